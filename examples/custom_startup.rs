@@ -0,0 +1,18 @@
+//! Embed lavagna, starting with a green blackboard and a thick yellow chalk:
+//!
+//! ```shell
+//! cargo run --example lavagna-custom-startup
+//! ```
+
+use bevy::color::palettes::css::YELLOW;
+use bevy::prelude::*;
+
+fn main() {
+    lavagna::run(lavagna::Opt {
+        chalk_color: Some(YELLOW),
+        chalk_line_width: Some(16),
+        background_color: Some(Color::srgb(0.1, 0.25, 0.15)),
+        // Command line arguments still apply for everything else
+        ..lavagna::options()
+    });
+}
@@ -0,0 +1,27 @@
+//! Run lavagna, writing telemetry events to `lavagna-telemetry.json`, one
+//! JSON object per line:
+//!
+//! ```shell
+//! cargo run --example lavagna-telemetry --features telemetry -- \
+//!     --collab-url ws://lavagna.alepez.dev:3536/demo
+//! ```
+//!
+//! Release builds only keep error events, run a debug build to see them all.
+
+use std::fs::File;
+use std::sync::Mutex;
+
+const TELEMETRY_PATH: &str = "lavagna-telemetry.json";
+
+fn main() {
+    let file = File::create(TELEMETRY_PATH).expect("cannot create telemetry file");
+
+    // Installed before the app, so bevy keeps this subscriber instead of its own
+    tracing_subscriber::fmt()
+        .json()
+        .with_max_level(tracing::Level::DEBUG)
+        .with_writer(Mutex::new(file))
+        .init();
+
+    lavagna::run(lavagna::options());
+}
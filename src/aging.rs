@@ -0,0 +1,156 @@
+#![allow(clippy::needless_pass_by_value)]
+
+use bevy::prelude::*;
+use bevy::time::common_conditions::on_timer;
+use bevy::utils::Duration;
+use bevy_prototype_lyon::prelude::Stroke;
+
+use crate::drawing::{Completed, StrokeMeta};
+use crate::Chalk;
+
+/// Old strokes fade toward the background, like chalk on a blackboard
+pub(crate) struct StrokeAgingPlugin;
+
+impl Plugin for StrokeAgingPlugin {
+    fn build(&self, app: &mut App) {
+        // Fading is slow, no need to update strokes every frame
+        let update_period = Duration::from_secs(10);
+
+        app.init_resource::<StrokeAgingConfig>()
+            .add_event::<ToggleStrokeAgingEvent>()
+            .add_systems(Update, handle_toggle_event)
+            .add_systems(
+                Update,
+                age_strokes
+                    .run_if(on_timer(update_period).or_else(resource_changed::<StrokeAgingConfig>)),
+            );
+    }
+}
+
+#[derive(Debug, Resource, Clone, Copy)]
+pub(crate) struct StrokeAgingConfig {
+    enabled: bool,
+    /// Time after which the stroke alpha is halved
+    half_life_seconds: f64,
+    /// Strokes never become fully invisible
+    min_alpha: f32,
+}
+
+impl Default for StrokeAgingConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            half_life_seconds: 300.0,
+            min_alpha: 0.2,
+        }
+    }
+}
+
+impl StrokeAgingConfig {
+    #[allow(clippy::cast_possible_truncation)]
+    fn alpha(&self, initial_alpha: f32, age_seconds: f64) -> f32 {
+        if !self.enabled {
+            return initial_alpha;
+        }
+
+        let min_alpha = self.min_alpha.min(initial_alpha);
+
+        // No half life, strokes are faded at once
+        if self.half_life_seconds <= 0.0 {
+            return min_alpha;
+        }
+
+        let decay = 0.5_f64.powf(age_seconds.max(0.0) / self.half_life_seconds) as f32;
+
+        (initial_alpha * decay).max(min_alpha)
+    }
+}
+
+#[derive(Event)]
+pub(crate) struct ToggleStrokeAgingEvent;
+
+fn handle_toggle_event(
+    mut events: EventReader<ToggleStrokeAgingEvent>,
+    mut config: ResMut<StrokeAgingConfig>,
+) {
+    for _ in events.read() {
+        config.enabled = !config.enabled;
+    }
+}
+
+/// Only completed strokes are faded, the pending ones are always opaque
+fn age_strokes(
+    config: Res<StrokeAgingConfig>,
+    time: Res<Time>,
+    mut strokes: Query<(&mut Stroke, &Chalk, &StrokeMeta), With<Completed>>,
+) {
+    let now = time.elapsed_seconds_f64();
+
+    for (mut stroke, chalk, meta) in &mut strokes {
+        let age = now - f64::from(meta.created_at());
+        let alpha = config.alpha(chalk.color.alpha, age);
+
+        stroke.color = Srgba {
+            alpha,
+            ..chalk.color
+        }
+        .into();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn enabled() -> StrokeAgingConfig {
+        StrokeAgingConfig {
+            enabled: true,
+            ..default()
+        }
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-6, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn new_stroke_keeps_its_alpha() {
+        assert_close(enabled().alpha(0.8, 0.0), 0.8);
+    }
+
+    #[test]
+    fn alpha_is_halved_every_half_life() {
+        let config = enabled();
+
+        assert_close(config.alpha(1.0, config.half_life_seconds), 0.5);
+        assert_close(config.alpha(1.0, 2.0 * config.half_life_seconds), 0.25);
+    }
+
+    #[test]
+    fn old_stroke_stops_at_min_alpha() {
+        let config = enabled();
+
+        assert_close(config.alpha(1.0, 1_000_000.0), config.min_alpha);
+    }
+
+    #[test]
+    fn faint_stroke_is_not_raised_to_min_alpha() {
+        assert_close(enabled().alpha(0.1, 1_000_000.0), 0.1);
+    }
+
+    #[test]
+    fn zero_half_life_fades_at_once() {
+        let config = StrokeAgingConfig {
+            half_life_seconds: 0.0,
+            ..enabled()
+        };
+
+        assert_close(config.alpha(1.0, 0.0), config.min_alpha);
+        assert_close(config.alpha(1.0, 10.0), config.min_alpha);
+    }
+
+    #[test]
+    fn disabled_aging_keeps_alpha() {
+        assert_close(StrokeAgingConfig::default().alpha(0.8, 1_000_000.0), 0.8);
+    }
+}
@@ -1,3 +1,4 @@
+use crate::network_config;
 use crate::CollabOpt;
 use crate::Opt;
 use clap::Parser;
@@ -10,28 +11,43 @@ struct Args {
     collab_url: Option<String>,
     #[clap(short = 'i', long)]
     collab_id: Option<u16>,
+    /// Ignore undo requests from peers
+    #[clap(long)]
+    deny_remote_undo: bool,
     #[clap(long)]
     show_debug_pane: bool,
     #[clap(short = 'v', long)]
     verbose: bool,
     #[clap(long)]
     ui: bool,
+    /// Join as a read-only observer
     #[clap(long)]
-    width: Option<String>,
-    #[clap(long)]
-    height: Option<String>,
+    spectate: bool,
+    /// Window width, in logical pixels
+    #[clap(long, value_parser = clap::value_parser!(u32).range(64..=7680))]
+    width: Option<u32>,
+    /// Window height, in logical pixels
+    #[clap(long, value_parser = clap::value_parser!(u32).range(64..=7680))]
+    height: Option<u32>,
 }
 
 /// On native, options are read from command line arguments
 pub(crate) fn options_from_args() -> Opt {
     let args = Args::parse();
+    let (network_config, network_config_error) = match network_config::load() {
+        Ok(network_config) => (network_config, None),
+        Err(err) => (network_config::WebRtcConfig::default(), Some(err)),
+    };
 
     // If collab-url is set, then collab-id must be set too. Randomize it if not.
-    let collab = if let Some(collab_url) = args.collab_url {
+    let collab_url = args.collab_url.or(network_config.signaling_url);
+    let collab = if let Some(collab_url) = collab_url {
         let collab_id = args.collab_id.unwrap_or_else(rand::random);
         Some(CollabOpt {
             url: collab_url,
             collab_id,
+            allow_remote_undo: !args.deny_remote_undo,
+            ice_server: network_config.ice_server,
         })
     } else {
         None
@@ -42,5 +58,10 @@ pub(crate) fn options_from_args() -> Opt {
         show_debug_pane: args.show_debug_pane,
         verbose: args.verbose,
         ui: args.ui,
+        spectate: args.spectate,
+        width: args.width,
+        height: args.height,
+        network_config_error,
+        ..Opt::default()
     }
 }
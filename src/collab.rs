@@ -2,16 +2,19 @@
 #![allow(clippy::cast_possible_truncation)]
 #![allow(clippy::module_name_repetitions)]
 
-use crate::drawing::{make_chalk, ClearEvent};
-use crate::{Chalk, Stats};
+#[cfg(feature = "telemetry")]
+use crate::drawing::Completed;
+use crate::drawing::{make_chalk, ClearEvent, ClearTarget, History};
+use crate::{AppMode, Chalk, Stats};
 use bevy::prelude::*;
 use bevy::utils::{Duration, HashMap, Instant};
+use bevy_matchbox::matchbox_socket::{RtcIceServerConfig, WebRtcSocket};
 use bevy_matchbox::prelude::*;
 use bevy_prototype_lyon::prelude::{GeometryBuilder, ShapeBundle, Stroke};
 use bevy_prototype_lyon::shapes;
 use serde::{Deserialize, Serialize};
 
-use crate::local_chalk::LocalChalk;
+use crate::local_chalk::{LocalChalk, StrokeUndoneEvent};
 
 pub(crate) struct CollabPlugin {
     opt: CollabPluginOpt,
@@ -21,6 +24,26 @@ pub(crate) struct CollabPlugin {
 pub struct CollabPluginOpt {
     pub url: String,
     pub collab_id: u16,
+    pub allow_remote_undo: bool,
+    pub ice_server: Option<IceServer>,
+}
+
+/// A STUN or TURN server, used to connect peers behind a NAT
+#[derive(Debug, Clone, Deserialize)]
+pub struct IceServer {
+    pub urls: Vec<String>,
+    pub username: Option<String>,
+    pub credential: Option<String>,
+}
+
+impl From<&IceServer> for RtcIceServerConfig {
+    fn from(ice_server: &IceServer) -> Self {
+        Self {
+            urls: ice_server.urls.clone(),
+            username: ice_server.username.clone(),
+            credential: ice_server.credential.clone(),
+        }
+    }
 }
 
 impl CollabPlugin {
@@ -31,17 +54,38 @@ impl CollabPlugin {
 
 impl Plugin for CollabPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
-        let socket = MatchboxSocket::new_reliable(&self.opt.url);
+        let mut socket = WebRtcSocket::builder(&self.opt.url).add_reliable_channel();
+        if let Some(ice_server) = &self.opt.ice_server {
+            socket = socket.ice_server(ice_server.into());
+        }
+        let socket = MatchboxSocket::from(socket);
         let collab_id = CollabId(self.opt.collab_id);
         let room = Room::new(socket, collab_id);
         app.insert_resource(room);
+        app.insert_resource(RemoteUndoPolicy {
+            allow: self.opt.allow_remote_undo,
+        });
 
         app.add_systems(Update, room_system);
         app.add_systems(Update, emit_events);
         app.add_systems(Update, receive_events);
         app.add_systems(Update, handle_clear_event);
+        app.add_systems(Update, handle_undo_event);
         app.add_systems(Update, update_peer_cursor_visibility);
         app.add_systems(Update, update_stats);
+
+        #[cfg(feature = "telemetry")]
+        {
+            use bevy::time::common_conditions::on_timer;
+
+            app.insert_resource(SessionTelemetry {
+                room: self.opt.url.clone(),
+            });
+            app.add_systems(
+                Update,
+                log_session_telemetry.run_if(on_timer(TELEMETRY_PERIOD)),
+            );
+        }
     }
 }
 
@@ -61,8 +105,10 @@ fn receive_events(
     mut commands: Commands,
     mut room: ResMut<Room>,
     mut chalk_q: Query<&mut Chalk>,
+    mut history_q: Query<&mut History>,
     mut cursor_q: Query<(&mut Transform, &mut Stroke, &mut PeerCursor), With<PeerCursor>>,
     mut clear_event: EventWriter<ClearEvent>,
+    remote_undo: Res<RemoteUndoPolicy>,
 ) {
     // This is needed, otherwise it can hangs forever when the connection is not established
     if !room.is_ok() {
@@ -83,8 +129,28 @@ fn receive_events(
             ),
             Event::Release => handle_release(src, &room, &mut chalk_q),
             Event::Clear => {
-                clear_event.send(ClearEvent::local_only());
+                clear_event.send(ClearEvent::local_only(ClearTarget::All));
+            }
+            Event::ClearRegion(e) => {
+                clear_event.send(ClearEvent::local_only(ClearTarget::Region(e.into())));
+            }
+            Event::Undo if remote_undo.allow => {
+                handle_undo(&mut commands, src, &room, &mut history_q);
             }
+            Event::Undo => {}
+        }
+    }
+}
+
+fn handle_undo(
+    commands: &mut Commands,
+    src: CollabId,
+    room: &Room,
+    history_q: &mut Query<&mut History>,
+) {
+    if let Some(peer) = room.peers.0.get(&src) {
+        if let Ok(mut history) = history_q.get_mut(peer.chalk) {
+            history.undo(commands);
         }
     }
 }
@@ -142,9 +208,10 @@ impl From<&Chalk> for MoveEvent {
     fn from(chalk: &Chalk) -> Self {
         Self {
             color: color_to_u32(chalk.color),
-            x: chalk.x as i16,
-            y: chalk.y as i16,
-            line_width: chalk.line_width as u8,
+            // Peers get whole pixels
+            x: chalk.x.round() as i16,
+            y: chalk.y.round() as i16,
+            line_width: chalk.rounded_line_width() as u8,
             pressed: chalk.pressed,
         }
     }
@@ -197,7 +264,7 @@ impl Peer {
 }
 
 #[derive(Resource)]
-struct Room {
+pub(crate) struct Room {
     socket: MatchboxSocket<SingleChannel>,
     collab_id: CollabId,
     peers: Peers,
@@ -220,6 +287,8 @@ impl Room {
         let peers: Vec<_> = self.socket.connected_peers().collect();
         let mut payload = Vec::new();
         ciborium::ser::into_writer(&event, &mut payload).unwrap();
+        #[cfg(feature = "telemetry")]
+        tracing::debug!(bytes = payload.len(), peers = peers.len(), "sent packet");
         for peer in peers {
             self.socket.send(payload.clone().into(), peer);
         }
@@ -244,6 +313,11 @@ enum Event {
     Move(MoveEvent),
     Release,
     Clear,
+    ClearRegion(ClearRegionEvent),
+    /// Undo the last stroke of the sender. Peers undo it as soon as the event
+    /// is received, so undoing while other peers draw may remove strokes in a
+    /// different order than the sender sees them.
+    Undo,
 }
 
 #[derive(Debug, Serialize, Deserialize, Copy, Clone)]
@@ -255,6 +329,38 @@ struct MoveEvent {
     pressed: bool,
 }
 
+/// Strokes overlapping this rectangle are removed
+#[derive(Debug, Serialize, Deserialize, Copy, Clone)]
+struct ClearRegionEvent {
+    min_x: i16,
+    min_y: i16,
+    max_x: i16,
+    max_y: i16,
+}
+
+impl From<Rect> for ClearRegionEvent {
+    #[allow(clippy::cast_possible_truncation)]
+    fn from(rect: Rect) -> Self {
+        Self {
+            min_x: rect.min.x.floor() as i16,
+            min_y: rect.min.y.floor() as i16,
+            max_x: rect.max.x.ceil() as i16,
+            max_y: rect.max.y.ceil() as i16,
+        }
+    }
+}
+
+impl From<ClearRegionEvent> for Rect {
+    fn from(e: ClearRegionEvent) -> Self {
+        Rect::new(
+            f32::from(e.min_x),
+            f32::from(e.min_y),
+            f32::from(e.max_x),
+            f32::from(e.max_y),
+        )
+    }
+}
+
 #[derive(Debug, Serialize, Deserialize, Copy, Clone)]
 struct AddressedEvent {
     src: CollabId,
@@ -278,18 +384,91 @@ fn room_system(mut room: ResMut<Room>) {
     };
     for (peer, new_state) in peers {
         match new_state {
-            PeerState::Connected => info!("peer {peer:?} connected"),
-            PeerState::Disconnected => info!("peer {peer:?} disconnected"),
+            PeerState::Connected => {
+                info!("peer {peer:?} connected");
+                #[cfg(feature = "telemetry")]
+                tracing::info!(peer_id = ?peer, "telemetry: peer connected");
+            }
+            PeerState::Disconnected => {
+                info!("peer {peer:?} disconnected");
+                #[cfg(feature = "telemetry")]
+                tracing::info!(peer_id = ?peer, "telemetry: peer disconnected");
+            }
         }
     }
 }
 
-fn handle_clear_event(mut events: EventReader<ClearEvent>, mut room: ResMut<Room>) {
-    let clear = events.read().filter(|e| e.must_be_forwarded()).count() > 0;
+/// Run condition, true while collaborating. Local only strokes would make
+/// the blackboards of the peers diverge.
+pub(crate) fn in_room(room: Option<Res<Room>>) -> bool {
+    room.is_some()
+}
 
-    if clear {
-        room.send(Event::Clear);
+fn handle_clear_event(
+    mut events: EventReader<ClearEvent>,
+    mut room: ResMut<Room>,
+    mode: Res<AppMode>,
+) {
+    // Spectators can clear their own blackboard, not the peers' ones
+    if *mode != AppMode::Drawing {
+        return;
     }
+
+    for event in events.read().filter(|e| e.must_be_forwarded()) {
+        match event.target() {
+            ClearTarget::All => room.send(Event::Clear),
+            ClearTarget::Region(rect) => room.send(Event::ClearRegion(rect.into())),
+        }
+    }
+}
+
+/// Only strokes are undone on the peers: flips, moves and clears of this
+/// blackboard are never sent, nor is bringing back erased strokes
+fn handle_undo_event(
+    mut events: EventReader<StrokeUndoneEvent>,
+    mut room: ResMut<Room>,
+    mode: Res<AppMode>,
+) {
+    // Spectators don't draw, they have no strokes of their own to undo
+    if *mode != AppMode::Drawing {
+        return;
+    }
+
+    for _ in events.read() {
+        room.send(Event::Undo);
+    }
+}
+
+/// Peers may be allowed to undo their strokes on this blackboard, or not
+#[derive(Resource)]
+struct RemoteUndoPolicy {
+    allow: bool,
+}
+
+/// Session health is logged with this period, see [`log_session_telemetry`]
+#[cfg(feature = "telemetry")]
+const TELEMETRY_PERIOD: Duration = Duration::from_secs(30);
+
+#[cfg(feature = "telemetry")]
+#[derive(Resource)]
+struct SessionTelemetry {
+    room: String,
+}
+
+/// Structured events, so that operators of signaling and relay servers can
+/// monitor the health of a session
+#[cfg(feature = "telemetry")]
+fn log_session_telemetry(
+    telemetry: Res<SessionTelemetry>,
+    stats: Res<Stats>,
+    strokes: Query<(), With<Completed>>,
+) {
+    tracing::info!(
+        room = %telemetry.room,
+        peers = stats.collab.peers,
+        strokes = strokes.iter().count(),
+        "session stats"
+    );
 }
 
 fn update_stats(room: Res<Room>, mut stats: ResMut<Stats>) {
@@ -0,0 +1,14 @@
+use bevy::color::palettes::css::{BLACK, WHITE};
+use bevy::color::{Luminance, Srgba};
+
+/// Relative luminance above which black has a better contrast than white
+const CONTRAST_THRESHOLD: f32 = 0.179;
+
+/// Black or white, whichever is more visible over the given color
+pub(crate) fn contrast_color(color: Srgba) -> Srgba {
+    if color.luminance() > CONTRAST_THRESHOLD {
+        BLACK
+    } else {
+        WHITE
+    }
+}
@@ -0,0 +1,203 @@
+#![allow(clippy::needless_pass_by_value)]
+
+use bevy::prelude::*;
+use bevy::window::PrimaryWindow;
+use bevy_prototype_lyon::prelude::Stroke;
+
+use crate::collab::Room;
+use crate::drawing::{
+    erase_stroke, Completed, EraserConfig, History, Polyline, StrokeHitTester, StrokeMeta,
+};
+use crate::local_chalk::{LocalChalk, LocalChalkFlag};
+use crate::ui::default_font;
+use crate::Chalk;
+
+/// Right click on a stroke opens a menu with its metadata and actions
+pub(crate) struct ContextMenuPlugin;
+
+impl Plugin for ContextMenuPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, close_context_menu)
+            .add_systems(Update, open_context_menu.after(close_context_menu))
+            .add_systems(Update, handle_context_menu_action);
+    }
+}
+
+const FONT_SIZE: f32 = 20.0;
+
+/// The open menu, for the given stroke
+#[derive(Component)]
+struct ContextMenu {
+    stroke: Entity,
+}
+
+#[derive(Component, Debug, Clone, Copy)]
+enum ContextMenuAction {
+    /// Erase the stroke, undo brings it back
+    Delete,
+    /// Paint the stroke with the local chalk color
+    Recolor,
+}
+
+impl ContextMenuAction {
+    fn label(self) -> &'static str {
+        match self {
+            Self::Delete => "Delete",
+            Self::Recolor => "Recolor",
+        }
+    }
+
+    fn verb(self) -> &'static str {
+        match self {
+            Self::Delete => "delete",
+            Self::Recolor => "recolor",
+        }
+    }
+}
+
+fn open_context_menu(
+    mut commands: Commands,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    chalk: Res<LocalChalk>,
+    hit_tester: StrokeHitTester,
+    meta_q: Query<&StrokeMeta>,
+    asset_server: Res<AssetServer>,
+) {
+    if !mouse_input.just_pressed(MouseButton::Right) {
+        return;
+    }
+
+    let Some(cursor_pos) = window_q.get_single().ok().and_then(Window::cursor_position) else {
+        return;
+    };
+
+    let chalk = chalk.get();
+    let point = Vec2::new(chalk.x, chalk.y);
+
+    let Some(stroke) = hit_tester.topmost(point) else {
+        return;
+    };
+
+    let created_at = meta_q
+        .get(stroke)
+        .map_or("--".to_owned(), |meta| format!("{:.0}s", meta.created_at()));
+
+    let font = default_font(&asset_server);
+    let text_style = TextStyle {
+        font,
+        font_size: FONT_SIZE,
+        color: Color::WHITE,
+    };
+
+    commands
+        .spawn((
+            ContextMenu { stroke },
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    left: Val::Px(cursor_pos.x),
+                    top: Val::Px(cursor_pos.y),
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(5.0)),
+                    row_gap: Val::Px(5.0),
+                    ..default()
+                },
+                background_color: BackgroundColor(Color::srgba(0.2, 0.2, 0.2, 0.9)),
+                // Above the toolbar
+                z_index: ZIndex::Global(1),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                format!("created at {created_at}"),
+                text_style.clone(),
+            ));
+
+            for action in [ContextMenuAction::Delete, ContextMenuAction::Recolor] {
+                parent
+                    .spawn((action, ButtonBundle::default()))
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(action.label(), text_style.clone()));
+                    });
+            }
+        });
+}
+
+/// Any other click closes the menu, as well as Escape
+fn close_context_menu(
+    mut commands: Commands,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    menu_q: Query<Entity, With<ContextMenu>>,
+    action_q: Query<&Interaction, With<ContextMenuAction>>,
+) {
+    let on_menu = action_q
+        .iter()
+        .any(|interaction| *interaction != Interaction::None);
+
+    let clicked_elsewhere =
+        mouse_input.any_just_pressed([MouseButton::Left, MouseButton::Right]) && !on_menu;
+
+    if !clicked_elsewhere && !keyboard_input.just_pressed(KeyCode::Escape) {
+        return;
+    }
+
+    for menu in &menu_q {
+        commands.entity(menu).despawn_recursive();
+    }
+}
+
+/// The actions are local, they are refused while collaborating: the
+/// blackboards of the peers would diverge
+#[allow(clippy::too_many_arguments)]
+fn handle_context_menu_action(
+    mut commands: Commands,
+    interaction_q: Query<(&Interaction, &ContextMenuAction), Changed<Interaction>>,
+    menu_q: Query<(Entity, &ContextMenu)>,
+    mut stroke_q: Query<(&mut Chalk, &mut Stroke, &Polyline, &StrokeMeta), With<Completed>>,
+    mut history_q: Query<&mut History, With<LocalChalkFlag>>,
+    local_chalk: Res<LocalChalk>,
+    eraser_config: Res<EraserConfig>,
+    room: Option<Res<Room>>,
+) {
+    let Ok((menu, ContextMenu { stroke: entity })) = menu_q.get_single() else {
+        return;
+    };
+
+    for (interaction, action) in &interaction_q {
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+
+        commands.entity(menu).despawn_recursive();
+
+        if room.is_some() {
+            log::warn!("cannot {} strokes while collaborating", action.verb());
+            return;
+        }
+
+        let Ok((mut chalk, mut stroke, polyline, meta)) = stroke_q.get_mut(*entity) else {
+            return;
+        };
+
+        match action {
+            ContextMenuAction::Delete => {
+                if let Ok(mut history) = history_q.get_single_mut() {
+                    history.push_deleted(polyline.clone(), *chalk, *meta);
+                }
+
+                // Undo restores it from the history, not from the ghost
+                erase_stroke(&mut commands, *entity, &eraser_config, false);
+            }
+            ContextMenuAction::Recolor => {
+                let color = local_chalk.color();
+                chalk.color = color;
+                stroke.color = color.with_alpha(stroke.color.alpha()).into();
+            }
+        }
+
+        return;
+    }
+}
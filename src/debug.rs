@@ -4,7 +4,12 @@ use bevy::prelude::*;
 
 use bevy::diagnostic::{Diagnostic, DiagnosticsStore, FrameTimeDiagnosticsPlugin};
 
+use crate::diagnostics::DRAWING_DIAGNOSTICS;
+#[cfg(feature = "debug")]
+use crate::drawing::{Completed, Pending};
 use crate::local_chalk::LocalChalk;
+#[cfg(feature = "debug")]
+use crate::MainCamera;
 use crate::Stats;
 
 pub(crate) struct DebugPlugin;
@@ -16,6 +21,195 @@ impl Plugin for DebugPlugin {
     }
 }
 
+/// Drawing diagnostics, at the top right corner
+pub(crate) struct DiagnosticsOverlayPlugin;
+
+impl Plugin for DiagnosticsOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, startup_diagnostics_overlay)
+            .add_systems(Update, update_diagnostics_overlay);
+    }
+}
+
+#[derive(Component)]
+struct DiagnosticsOverlayText;
+
+fn startup_diagnostics_overlay(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        TextBundle::from_section(
+            "--",
+            TextStyle {
+                font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+                font_size: 20.0,
+                color: Color::WHITE,
+            },
+        )
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(5.0),
+            right: Val::Px(15.0),
+            ..default()
+        }),
+        DiagnosticsOverlayText,
+    ));
+}
+
+fn update_diagnostics_overlay(
+    diagnostics: Res<DiagnosticsStore>,
+    mut text: Query<&mut Text, With<DiagnosticsOverlayText>>,
+) {
+    let Ok(mut text) = text.get_single_mut() else {
+        return;
+    };
+
+    let lines: Vec<String> = DRAWING_DIAGNOSTICS
+        .iter()
+        .map(|path| {
+            let name = path.as_str().trim_start_matches("lavagna/");
+            diagnostics
+                .get_measurement(path)
+                .map_or(format!("{name} --"), |m| format!("{name} {:.0}", m.value))
+        })
+        .collect();
+
+    text.sections[0].value = lines.join("\n");
+}
+
+/// World inspector, toggled with Ctrl+D
+#[cfg(feature = "debug")]
+pub(crate) struct InspectorPlugin;
+
+#[cfg(feature = "debug")]
+impl Plugin for InspectorPlugin {
+    fn build(&self, app: &mut App) {
+        use bevy_inspector_egui::quick::WorldInspectorPlugin;
+
+        app.init_resource::<InspectorVisible>()
+            .add_systems(Update, toggle_inspector)
+            .add_plugins(WorldInspectorPlugin::new().run_if(inspector_visible));
+    }
+}
+
+/// Entity counts and camera state, toggled with Ctrl+Shift+D. Useful to
+/// notice leaking pending strokes without a profiler.
+#[cfg(feature = "debug")]
+pub(crate) struct EntityHudPlugin;
+
+#[cfg(feature = "debug")]
+impl Plugin for EntityHudPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, startup_entity_hud)
+            .add_systems(Update, toggle_entity_hud)
+            .add_systems(Update, update_entity_hud);
+    }
+}
+
+#[cfg(feature = "debug")]
+#[derive(Component)]
+struct EntityHudText;
+
+#[cfg(feature = "debug")]
+fn startup_entity_hud(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.spawn((
+        TextBundle {
+            visibility: Visibility::Hidden,
+            ..TextBundle::from_section(
+                "--",
+                TextStyle {
+                    font: asset_server.load("fonts/FiraMono-Medium.ttf"),
+                    font_size: 20.0,
+                    color: Color::WHITE,
+                },
+            )
+        }
+        // Below the debug pane
+        .with_style(Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(110.0),
+            left: Val::Px(15.0),
+            ..default()
+        }),
+        EntityHudText,
+    ));
+}
+
+#[cfg(feature = "debug")]
+fn toggle_entity_hud(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut visibility_q: Query<&mut Visibility, With<EntityHudText>>,
+) {
+    let ctrl = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+    let shift = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+
+    if !(ctrl && shift && keyboard_input.just_pressed(KeyCode::KeyD)) {
+        return;
+    }
+
+    for mut visibility in &mut visibility_q {
+        *visibility = match *visibility {
+            Visibility::Hidden => Visibility::Visible,
+            _ => Visibility::Hidden,
+        };
+    }
+}
+
+#[cfg(feature = "debug")]
+fn update_entity_hud(
+    mut text: Query<(&mut Text, &Visibility), With<EntityHudText>>,
+    pending: Query<(), With<Pending>>,
+    completed: Query<(), With<Completed>>,
+    camera_q: Query<(&Transform, &OrthographicProjection), With<MainCamera>>,
+) {
+    let Ok((mut text, visibility)) = text.get_single_mut() else {
+        return;
+    };
+
+    if *visibility == Visibility::Hidden {
+        return;
+    }
+
+    let pending = pending.iter().count();
+    let completed = completed.iter().count();
+
+    let camera = camera_q.get_single().map_or("--".to_owned(), |(t, p)| {
+        let x = t.translation.x;
+        let y = t.translation.y;
+        let scale = p.scale;
+        format!("{x:+.0}:{y:+.0} x{scale:.2}")
+    });
+
+    text.sections[0].value = format!(
+        r#"pending {pending}
+completed {completed}
+camera {camera}
+"#
+    );
+}
+
+#[cfg(feature = "debug")]
+#[derive(Resource, Default)]
+struct InspectorVisible(bool);
+
+#[cfg(feature = "debug")]
+fn inspector_visible(visible: Res<InspectorVisible>) -> bool {
+    visible.0
+}
+
+#[cfg(feature = "debug")]
+fn toggle_inspector(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut visible: ResMut<InspectorVisible>,
+) {
+    let ctrl = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+
+    // Ctrl+Shift+D toggles the entity HUD instead
+    let shift = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+
+    if ctrl && !shift && keyboard_input.just_pressed(KeyCode::KeyD) {
+        visible.0 = !visible.0;
+    }
+}
+
 #[derive(Component)]
 struct DebugText;
 
@@ -62,7 +256,7 @@ fn update(
         let x = chalk.x;
         let y = chalk.y;
         let pressed = chalk.pressed;
-        format!("{x:+05}:{y:+05} {pressed}")
+        format!("{x:+07.1}:{y:+07.1} {pressed}")
     };
 
     let collab = { format!("{:?}", stats.collab) };
@@ -0,0 +1,73 @@
+#![allow(clippy::needless_pass_by_value)]
+
+use bevy::diagnostic::{Diagnostic, DiagnosticPath, Diagnostics, RegisterDiagnostic};
+use bevy::prelude::*;
+use bevy::time::common_conditions::on_timer;
+use bevy::utils::Duration;
+
+use crate::drawing::{Completed, Pending, Polyline, StrokeMeta};
+
+/// Drawing metrics, in the [`bevy::diagnostic::DiagnosticsStore`] along
+/// with the frame time. See the inspector or the debug pane.
+pub(crate) struct DrawingDiagnosticsPlugin;
+
+pub(crate) const STROKES_COMPLETED_PER_SECOND: DiagnosticPath =
+    DiagnosticPath::const_new("lavagna/strokes_completed_per_second");
+pub(crate) const PENDING_POINT_COUNT: DiagnosticPath =
+    DiagnosticPath::const_new("lavagna/pending_point_count");
+pub(crate) const COMPLETED_STROKE_COUNT: DiagnosticPath =
+    DiagnosticPath::const_new("lavagna/completed_stroke_count");
+pub(crate) const TOTAL_POLYLINE_VERTICES: DiagnosticPath =
+    DiagnosticPath::const_new("lavagna/total_polyline_vertices");
+
+/// Every path, in display order
+pub(crate) const DRAWING_DIAGNOSTICS: [DiagnosticPath; 4] = [
+    STROKES_COMPLETED_PER_SECOND,
+    PENDING_POINT_COUNT,
+    COMPLETED_STROKE_COUNT,
+    TOTAL_POLYLINE_VERTICES,
+];
+
+const SAMPLE_PERIOD: Duration = Duration::from_secs(1);
+
+impl Plugin for DrawingDiagnosticsPlugin {
+    fn build(&self, app: &mut App) {
+        for path in DRAWING_DIAGNOSTICS {
+            app.register_diagnostic(Diagnostic::new(path));
+        }
+
+        app.add_systems(Update, sample.run_if(on_timer(SAMPLE_PERIOD)));
+    }
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn sample(
+    mut diagnostics: Diagnostics,
+    pending: Query<&Polyline, With<Pending>>,
+    completed: Query<(&Polyline, &StrokeMeta), With<Completed>>,
+    time: Res<Time>,
+) {
+    let since = time.elapsed_seconds() - SAMPLE_PERIOD.as_secs_f32();
+
+    let recent = completed
+        .iter()
+        .filter(|(_, meta)| meta.created_at() > since)
+        .count();
+
+    diagnostics.add_measurement(&STROKES_COMPLETED_PER_SECOND, || {
+        recent as f64 / SAMPLE_PERIOD.as_secs_f64()
+    });
+
+    diagnostics.add_measurement(&PENDING_POINT_COUNT, || {
+        pending.iter().map(Polyline::point_count).sum::<usize>() as f64
+    });
+
+    diagnostics.add_measurement(&COMPLETED_STROKE_COUNT, || completed.iter().count() as f64);
+
+    diagnostics.add_measurement(&TOTAL_POLYLINE_VERTICES, || {
+        completed
+            .iter()
+            .map(|(polyline, _)| polyline.point_count())
+            .sum::<usize>() as f64
+    });
+}
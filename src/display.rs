@@ -0,0 +1,102 @@
+#![allow(clippy::needless_pass_by_value)]
+
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::Stroke;
+
+use crate::Chalk;
+
+/// Display options changing how strokes are rendered, without changing the
+/// strokes themselves: saved scenes and peers still get the chalk colors
+pub(crate) struct DisplayPlugin;
+
+impl Plugin for DisplayPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<DisplayConfig>()
+            .add_event::<ToggleInvertColorsEvent>()
+            .add_systems(Update, handle_toggle_invert_colors_event)
+            .add_systems(Update, update_background)
+            .add_systems(PostUpdate, apply_display_colors);
+    }
+}
+
+#[derive(Debug, Resource, Clone, Copy)]
+pub(crate) struct DisplayConfig {
+    invert_colors: bool,
+    /// Background while colors are inverted
+    background_color: Color,
+}
+
+impl Default for DisplayConfig {
+    fn default() -> Self {
+        Self {
+            invert_colors: false,
+            // Like a chalkboard
+            background_color: Color::srgb(0.15, 0.3, 0.2),
+        }
+    }
+}
+
+#[derive(Event)]
+pub(crate) struct ToggleInvertColorsEvent;
+
+fn handle_toggle_invert_colors_event(
+    mut events: EventReader<ToggleInvertColorsEvent>,
+    mut config: ResMut<DisplayConfig>,
+) {
+    for _ in events.read() {
+        config.invert_colors = !config.invert_colors;
+    }
+}
+
+/// The background from [`DrawingPlugin`](crate::DrawingPlugin) is kept,
+/// to be restored when colors are no longer inverted
+fn update_background(
+    config: Res<DisplayConfig>,
+    mut clear_color: ResMut<ClearColor>,
+    mut normal_background: Local<Option<Color>>,
+) {
+    if !config.is_changed() {
+        return;
+    }
+
+    if config.invert_colors {
+        normal_background.get_or_insert(clear_color.0);
+        clear_color.0 = config.background_color;
+    } else if let Some(color) = normal_background.take() {
+        clear_color.0 = color;
+    }
+}
+
+/// Stroke colors are set from the chalk color by several systems (drawing,
+/// aging, eraser ghosts), this runs after all of them. Only the alpha of the
+/// rendered color is kept.
+fn apply_display_colors(config: Res<DisplayConfig>, mut strokes: Query<(&mut Stroke, &Chalk)>) {
+    for (mut stroke, chalk) in &mut strokes {
+        if !config.is_changed() && !stroke.is_changed() {
+            continue;
+        }
+
+        let alpha = stroke.color.alpha();
+
+        let color = if config.invert_colors {
+            invert(chalk.color)
+        } else {
+            chalk.color
+        };
+
+        let color = Color::from(color.with_alpha(alpha));
+
+        if stroke.color != color {
+            stroke.color = color;
+        }
+    }
+}
+
+fn invert(color: Srgba) -> Srgba {
+    Srgba {
+        red: 1.0 - color.red,
+        green: 1.0 - color.green,
+        blue: 1.0 - color.blue,
+        alpha: color.alpha,
+    }
+}
@@ -1,98 +1,454 @@
 #![allow(clippy::needless_pass_by_value)]
 #![allow(clippy::cast_precision_loss)]
 
+use std::fmt::Write;
+
+use crate::local_chalk::{LocalChalkFlag, TouchChalk, UndoEvent};
+use crate::signal_processing::savitzky_golay;
 use crate::Chalk;
+use crate::MainCamera;
+use bevy::ecs::system::SystemParam;
+use bevy::utils::Duration;
 use bevy::{prelude::*, render::view::NoFrustumCulling};
+#[cfg(feature = "lavagna_serde")]
+use serde::{Deserialize, Serialize};
 
 use bevy_prototype_lyon::prelude::*;
 
 const POINTS_CHUNK_THRESHOLD: usize = 100;
 
-pub(crate) struct DrawingPlugin;
+/// Draw chalks and keep the completed strokes
+pub struct DrawingPlugin {
+    chunk_threshold: usize,
+    background_color: Color,
+}
+
+impl Default for DrawingPlugin {
+    fn default() -> Self {
+        Self {
+            chunk_threshold: POINTS_CHUNK_THRESHOLD,
+            background_color: Color::BLACK,
+        }
+    }
+}
+
+impl DrawingPlugin {
+    /// Pending strokes are completed in chunks of `threshold` points, so
+    /// that the pending mesh stays small. Each chunk starts from the last
+    /// point of the previous one, so at least 2 points are needed.
+    #[must_use]
+    pub fn with_chunk_threshold(mut self, threshold: usize) -> Self {
+        self.chunk_threshold = threshold.max(2);
+        self
+    }
+
+    /// The blackboard color, black by default
+    #[must_use]
+    pub fn with_background_color(mut self, color: Color) -> Self {
+        self.background_color = color;
+        self
+    }
+}
+
+/// Maximum number of points of a pending stroke, see [`DrawingPlugin::with_chunk_threshold`]
+#[derive(Debug, Resource, Clone, Copy)]
+struct ChunkThreshold(usize);
 
 impl Plugin for DrawingPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
         app.add_plugins(ShapePlugin)
+            .insert_resource(ChunkThreshold(self.chunk_threshold))
+            .insert_resource(ClearColor(self.background_color))
+            .init_resource::<CanvasBounds>()
+            .init_resource::<TessellationQuality>()
+            .init_resource::<NoiseFilterConfig>()
+            .init_resource::<ZOrigin>()
+            .init_resource::<EraserConfig>()
             .add_event::<ClearEvent>()
+            .add_event::<RebaseZOrderEvent>()
+            .add_event::<FlipCanvasEvent>()
+            .add_event::<TranslateCanvasEvent>()
+            .register_type::<Polyline>()
+            .register_type::<StrokeMeta>()
+            .register_type::<Completed>()
             .add_systems(Update, handle_clear_event)
-            .add_systems(Update, update);
+            .add_systems(Update, handle_flip_canvas_event)
+            .add_systems(Update, handle_translate_canvas_event)
+            .add_systems(Update, tick_eraser_ghosts)
+            .add_systems(Update, unerase_ghosts)
+            .add_systems(Update, restore_strokes)
+            .add_systems(Update, expire_pending_strokes.before(update))
+            .add_systems(Update, update)
+            .add_systems(Update, separate_stroke_depths.after(update))
+            .add_systems(Update, check_z_overflow.after(separate_stroke_depths))
+            .add_systems(Update, rebase_z_order.after(check_z_overflow))
+            .add_systems(Update, apply_tessellation_quality.after(update));
+    }
+}
+
+/// Maximum distance between the tessellated stroke and the ideal curve, in
+/// world units. Lower is smoother but generates more triangles; on low-end
+/// hardware a higher tolerance renders faster, with visibly faceted curves.
+/// Changing it updates the existing strokes too.
+#[derive(Debug, Resource, Clone, Copy)]
+pub struct TessellationQuality {
+    pub tolerance: f32,
+}
+
+impl Default for TessellationQuality {
+    fn default() -> Self {
+        Self { tolerance: 0.1 }
+    }
+}
+
+fn apply_tessellation_quality(quality: Res<TessellationQuality>, mut stroke_q: Query<&mut Stroke>) {
+    for mut stroke in &mut stroke_q {
+        if quality.is_changed() || stroke.is_added() {
+            stroke.options.tolerance = quality.tolerance;
+        }
     }
 }
 
-fn update(
+pub(crate) fn update(
     mut commands: Commands,
-    mut chalk_q: Query<(&mut Chalk, &mut Path, &mut Stroke, &mut Polyline), With<Pending>>,
+    mut chalk_q: Query<
+        (
+            &mut Chalk,
+            &mut Path,
+            &mut Stroke,
+            &mut Polyline,
+            &mut History,
+            &mut PendingTimeout,
+            Option<&PredictedPoint>,
+        ),
+        With<Pending>,
+    >,
     time: Res<Time>,
+    bounds: Res<CanvasBounds>,
+    chunk_threshold: Res<ChunkThreshold>,
+    noise_filter: Res<NoiseFilterConfig>,
 ) {
-    for (chalk, mut path, mut stroke, mut polyline) in &mut chalk_q {
+    for (chalk, mut path, mut stroke, mut polyline, mut history, mut timeout, predicted) in
+        &mut chalk_q
+    {
         let updated = chalk.pressed && chalk.updated;
 
         stroke.color = chalk.color.into();
-        stroke.options.line_width = chalk.line_width as f32;
+        stroke.options.line_width = chalk.line_width;
 
         if updated {
-            add_point(&mut polyline, &chalk);
+            add_point(&mut polyline, &chalk, &bounds);
+            timeout.last_updated = time.elapsed_seconds_f64();
         }
 
-        let chunk_completed = polyline.points.len() >= POINTS_CHUNK_THRESHOLD;
-        let just_released = chalk.just_released && !polyline.points.is_empty();
+        let chunk_completed = polyline.point_count() >= chunk_threshold.0;
+        let just_released = chalk.just_released && !polyline.is_empty();
         let completed = just_released || chunk_completed;
 
         if completed {
-            complete_pending_path(&mut polyline, &mut commands, &chalk, &time);
+            let stroke = complete_pending_path(&mut polyline, &mut commands, &chalk, &time);
+            history.push(stroke);
 
             if chunk_completed {
-                add_point(&mut polyline, &chalk);
+                add_point(&mut polyline, &chalk, &bounds);
             }
         }
 
+        if chalk.just_released {
+            history.end_stroke();
+        }
+
+        // The predicted point is shown, but never committed
+        let predicted = predicted
+            .and_then(|predicted| predicted.0)
+            .filter(|_| chalk.pressed && !polyline.is_empty());
+
         // Regenerate mesh from list of points
-        *path = Path::from(&*polyline);
+        *path = preview_path(&polyline, predicted, &noise_filter);
+    }
+}
+
+/// Smoothing of noisy input (cheap touch screens or tablets). Only the
+/// preview of the pending stroke is filtered, the stored points are not.
+#[derive(Debug, Resource, Clone, Copy)]
+pub struct NoiseFilterConfig {
+    pub enabled: bool,
+    /// Number of points each point is fitted on, odd: 5 or 7 work well
+    pub window: usize,
+}
+
+impl Default for NoiseFilterConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            window: 5,
+        }
+    }
+}
+
+fn preview_path(polyline: &Polyline, predicted: Option<Vec2>, filter: &NoiseFilterConfig) -> Path {
+    if !filter.enabled && predicted.is_none() {
+        return Path::from(polyline);
+    }
+
+    let mut preview = Polyline {
+        points: if filter.enabled {
+            savitzky_golay(&polyline.points, filter.window, 2)
+        } else {
+            polyline.points.clone()
+        },
+    };
+
+    preview.points.extend(predicted);
+
+    Path::from(&preview)
+}
+
+/// A peer disconnecting mid-stroke never sends the release, its pending
+/// stroke would stay on screen forever. Chalks pressed without moving for too
+/// long are released, so that the stroke is completed. The local chalk and
+/// the chalks of extra touches are left alone, holding them still is not a
+/// disconnection.
+fn expire_pending_strokes(
+    mut chalk_q: Query<
+        (&mut Chalk, &Polyline, &PendingTimeout),
+        (With<Pending>, Without<LocalChalkFlag>, Without<TouchChalk>),
+    >,
+    time: Res<Time>,
+) {
+    let now = time.elapsed_seconds_f64();
+
+    for (mut chalk, polyline, timeout) in &mut chalk_q {
+        if !chalk.pressed || polyline.is_empty() || !timeout.is_expired(now) {
+            continue;
+        }
+
+        log::info!("pending stroke timed out after {}s", timeout.timeout_secs);
+        chalk.pressed = false;
+        chalk.just_released = true;
     }
 }
 
-fn add_point(polyline: &mut Polyline, chalk: &Chalk) {
-    let new_point = Vec2::new(chalk.x as f32, chalk.y as f32);
+/// Repeated points are skipped, zero length segments break tessellation
+fn add_point(polyline: &mut Polyline, chalk: &Chalk, bounds: &CanvasBounds) {
+    let new_point = bounds.clamp(Vec2::new(chalk.x, chalk.y));
+
+    if polyline.last_point() == Some(new_point) {
+        return;
+    }
+
     polyline.points.push(new_point);
 }
 
-fn z_from_time(time: &Time) -> f32 {
+/// The canvas is infinite in principle, but coordinates drifting toward
+/// `f32::MAX` break rendering. Points outside these bounds are clamped.
+/// The default extent is plenty for a blackboard, increase it if needed.
+#[derive(Debug, Resource, Clone, Copy)]
+pub(crate) struct CanvasBounds {
+    min: Vec2,
+    max: Vec2,
+}
+
+impl Default for CanvasBounds {
+    fn default() -> Self {
+        Self {
+            min: Vec2::splat(-100_000.0),
+            max: Vec2::splat(100_000.0),
+        }
+    }
+}
+
+impl CanvasBounds {
+    pub(crate) fn clamp(&self, point: Vec2) -> Vec2 {
+        point.clamp(self.min, self.max)
+    }
+}
+
+fn z_from_time(t: f32) -> f32 {
     const MAX_Z: f32 = 500.0;
     const MAX_TIME: f32 = 10_000.0;
 
-    let t = time.elapsed_seconds();
     let step = MAX_Z / MAX_TIME;
     t * step
 }
 
+/// Depth offset separating strokes completed at the same time, small enough
+/// not to reorder them with the following strokes
+const Z_STEP: f32 = 0.000_1;
+
+/// Completed strokes must stay below the pending ones (998)
+const REBASE_Z: f32 = 995.0;
+
+/// After a rebase, completed strokes are spread up to this depth
+const REBASED_MAX_Z: f32 = 495.0;
+
+/// Depth from time grows without bounds, it is shifted down by this offset
+/// after each rebase, see [`RebaseZOrderEvent`]
+#[derive(Debug, Resource, Default)]
+struct ZOrigin(f32);
+
+/// Completed strokes reached [`REBASE_Z`] (after about 5 hours), their depths
+/// are compressed back to `0..REBASED_MAX_Z`, keeping their order
+#[derive(Event)]
+struct RebaseZOrderEvent;
+
+/// Strokes completed in the same frame (by several peers, or restored from a
+/// scene) get the same depth and flicker where they overlap. New strokes are
+/// moved up until their depth is unique.
+fn separate_stroke_depths(
+    mut stroke_q: Query<&mut Transform, With<Completed>>,
+    z_origin: Res<ZOrigin>,
+) {
+    let mut depths = Vec::new();
+    let mut added = 0;
+
+    // New and restored strokes get their transform when their mesh is built,
+    // strokes brought back from an eraser ghost keep theirs
+    for transform in &mut stroke_q {
+        if transform.is_added() {
+            added += 1;
+        } else {
+            depths.push(transform.translation.z);
+        }
+    }
+
+    if added == 0 {
+        return;
+    }
+
+    for mut transform in &mut stroke_q {
+        if !transform.is_added() {
+            continue;
+        }
+
+        // Strokes older than the last rebase (an undone clear, a loaded
+        // scene) would end up behind the background
+        let mut z = (transform.translation.z - z_origin.0).max(0.0);
+        let mut moved = z_origin.0 > 0.0;
+
+        while depths.iter().any(|depth| (depth - z).abs() < f32::EPSILON) {
+            z += Z_STEP;
+            moved = true;
+        }
+
+        if moved {
+            transform.translation.z = z;
+        }
+
+        depths.push(z);
+    }
+}
+
+/// Only new and restored strokes are checked, the depth of the others does
+/// not change
+fn check_z_overflow(
+    stroke_q: Query<&Transform, (With<Completed>, Added<Transform>)>,
+    mut rebase_events: EventWriter<RebaseZOrderEvent>,
+) {
+    if stroke_q.iter().any(|t| t.translation.z > REBASE_Z) {
+        rebase_events.send(RebaseZOrderEvent);
+    }
+}
+
+fn rebase_z_order(
+    mut events: EventReader<RebaseZOrderEvent>,
+    mut stroke_q: Query<&mut Transform, With<Completed>>,
+    mut z_origin: ResMut<ZOrigin>,
+    time: Res<Time>,
+) {
+    if events.read().count() == 0 {
+        return;
+    }
+
+    let mut transforms: Vec<_> = stroke_q.iter_mut().collect();
+    transforms.sort_by(|a, b| a.translation.z.total_cmp(&b.translation.z));
+
+    let step = REBASED_MAX_Z / transforms.len() as f32;
+
+    for (i, transform) in transforms.iter_mut().enumerate() {
+        transform.translation.z = i as f32 * step;
+    }
+
+    // New strokes continue right above the rebased ones
+    z_origin.0 = z_from_time(time.elapsed_seconds()) - REBASED_MAX_Z;
+
+    log::info!("rebased the depth of {} strokes", transforms.len());
+}
+
 fn complete_pending_path(
     polyline: &mut Polyline,
     commands: &mut Commands,
     chalk: &Chalk,
     time: &Time,
-) {
-    let path = Path::from(&*polyline);
+) -> Entity {
+    polyline.deduplicate_consecutive();
+
+    let record = StrokeRecord {
+        polyline: polyline.clone(),
+        chalk: *chalk,
+        meta: StrokeMeta {
+            created_at: time.elapsed_seconds(),
+        },
+    };
+
+    // The line is complete, we spawn a fresh mesh, which will persist
+    let entity = spawn_stroke(commands, record);
+
+    polyline.points.clear();
 
-    let z = z_from_time(time);
+    entity
+}
+
+fn spawn_stroke(commands: &mut Commands, record: StrokeRecord) -> Entity {
+    commands.spawn(stroke_bundle(record)).id()
+}
+
+fn stroke_bundle(record: StrokeRecord) -> impl Bundle {
+    let StrokeRecord {
+        polyline,
+        chalk,
+        meta,
+    } = record;
+
+    let path = Path::from(&polyline);
+
+    let z = z_from_time(meta.created_at);
 
     let transform = Transform {
         translation: Vec3::new(0., 0., z),
         ..default()
     };
 
-    // The line is complete, we spawn a fresh mesh, which will persist
-    commands.spawn((
+    (
         ShapeBundle {
             path,
             spatial: transform.into(),
             ..default()
         },
-        Stroke::new(chalk.color, chalk.line_width as f32),
+        Stroke::new(chalk.color, chalk.line_width),
         Fill::color(Color::NONE),
+        polyline,
+        chalk,
+        meta,
         Completed,
-    ));
+    )
+}
 
-    polyline.points.clear();
+/// Completed strokes can be inserted without their mesh, for example when
+/// they are loaded from a scene. Here the mesh is generated.
+fn restore_strokes(
+    mut commands: Commands,
+    strokes: Query<(Entity, &Polyline, &Chalk, &StrokeMeta), (With<Completed>, Without<Path>)>,
+) {
+    for (entity, polyline, chalk, meta) in &strokes {
+        let record = StrokeRecord {
+            polyline: polyline.clone(),
+            chalk: *chalk,
+            meta: *meta,
+        };
+
+        commands.entity(entity).insert(stroke_bundle(record));
+    }
 }
 
 pub(crate) fn make_chalk(chalk: Chalk) -> impl Bundle {
@@ -114,23 +470,432 @@ pub(crate) fn make_chalk(chalk: Chalk) -> impl Bundle {
         Stroke::new(Color::WHITE, 10.0),
         Fill::color(Color::NONE),
         Polyline::default(),
+        History::default(),
         Pending,
+        PendingTimeout::default(),
         NoFrustumCulling,
         chalk,
     )
 }
 
-#[derive(Debug, Component, Default)]
-struct Completed;
+#[derive(Debug, Component, Default, Reflect)]
+#[reflect(Component)]
+pub(crate) struct Completed;
 
 #[derive(Debug, Component, Default)]
 pub(crate) struct Pending;
 
-#[derive(Debug, Clone, Component, Default)]
-pub(crate) struct Polyline {
+/// When the pending stroke last got a point, in seconds since startup
+#[derive(Debug, Component, Clone, Copy)]
+pub(crate) struct PendingTimeout {
+    pub(crate) last_updated: f64,
+    pub(crate) timeout_secs: f64,
+}
+
+impl Default for PendingTimeout {
+    fn default() -> Self {
+        Self {
+            last_updated: 0.0,
+            timeout_secs: 5.0,
+        }
+    }
+}
+
+impl PendingTimeout {
+    fn is_expired(&self, now: f64) -> bool {
+        now - self.last_updated > self.timeout_secs
+    }
+}
+
+/// Where the chalk is expected to be, ahead of the last known position.
+/// Drawn at the end of the pending stroke, to hide the display latency.
+#[derive(Debug, Component, Default)]
+pub(crate) struct PredictedPoint(pub(crate) Option<Vec2>);
+
+#[derive(Debug, Clone, Component, Default, Reflect)]
+#[reflect(Component)]
+#[cfg_attr(feature = "lavagna_serde", derive(Serialize, Deserialize))]
+pub struct Polyline {
     points: Vec<Vec2>,
 }
 
+impl Polyline {
+    #[must_use]
+    pub fn point_count(&self) -> usize {
+        self.points.len()
+    }
+
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.points.is_empty()
+    }
+
+    #[must_use]
+    pub fn first_point(&self) -> Option<Vec2> {
+        self.points.first().copied()
+    }
+
+    #[must_use]
+    pub fn last_point(&self) -> Option<Vec2> {
+        self.points.last().copied()
+    }
+
+    /// Remove the points repeating the previous one, see
+    /// [`Polyline::deduplicate_consecutive_epsilon`]
+    pub fn deduplicate_consecutive(&mut self) {
+        self.deduplicate_consecutive_epsilon(f32::EPSILON);
+    }
+
+    /// Remove the points closer than `eps` to the previous one on both axes,
+    /// so that the path has no zero length segments
+    pub fn deduplicate_consecutive_epsilon(&mut self, eps: f32) {
+        self.points.dedup_by(|point, previous| {
+            (point.x - previous.x).abs() < eps && (point.y - previous.y).abs() < eps
+        });
+    }
+
+    /// Move all the points by `delta`
+    pub fn translate(&mut self, delta: Vec2) {
+        for point in &mut self.points {
+            *point += delta;
+        }
+    }
+
+    /// Points from `[x0, y0, x1, y1, ...]`, `None` if the length is odd
+    #[must_use]
+    pub fn from_flat_coords(coords: &[f32]) -> Option<Polyline> {
+        if coords.len() % 2 != 0 {
+            return None;
+        }
+
+        let points = coords
+            .chunks_exact(2)
+            .map(|xy| Vec2::new(xy[0], xy[1]))
+            .collect();
+
+        Some(Polyline { points })
+    }
+
+    /// Total arc length, 0 for polylines with fewer than two points
+    #[must_use]
+    pub fn length(&self) -> f32 {
+        self.points
+            .windows(2)
+            .map(|segment| segment[0].distance(segment[1]))
+            .sum()
+    }
+
+    /// Point at arc length `s` from the start, clamped to the polyline ends
+    #[must_use]
+    pub fn point_at_arc_length(&self, s: f32) -> Vec2 {
+        let Some(first) = self.first_point() else {
+            return Vec2::ZERO;
+        };
+
+        let mut remaining = s.max(0.0);
+        let mut last = first;
+
+        for segment in self.points.windows(2) {
+            let (start, end) = (segment[0], segment[1]);
+            let segment_length = start.distance(end);
+
+            if remaining <= segment_length && segment_length > 0.0 {
+                return start.lerp(end, remaining / segment_length);
+            }
+
+            remaining -= segment_length;
+            last = end;
+        }
+
+        last
+    }
+
+    /// Same points, from the end to the start
+    #[must_use]
+    pub fn reverse(&self) -> Polyline {
+        let mut reversed = self.clone();
+        reversed.reversed_in_place();
+        reversed
+    }
+
+    pub fn reversed_in_place(&mut self) {
+        self.points.reverse();
+    }
+
+    /// Points of `self` followed by points of `other`, for example an arrow
+    /// shaft and its head
+    #[must_use]
+    pub fn concat(&self, other: &Polyline) -> Polyline {
+        Polyline {
+            points: [self.points.as_slice(), other.points.as_slice()].concat(),
+        }
+    }
+
+    /// Distance from `point` to the nearest segment, `None` if empty. A
+    /// single point is a degenerate segment.
+    pub(crate) fn distance_to(&self, point: Vec2) -> Option<f32> {
+        let first = self.first_point()?;
+
+        let segments = self
+            .points
+            .windows(2)
+            .map(|segment| (segment[0], segment[1]));
+
+        std::iter::once((first, first))
+            .chain(segments)
+            .map(|(start, end)| distance_to_segment(point, start, end))
+            .min_by(f32::total_cmp)
+    }
+
+    /// Smallest rectangle containing all points, `None` if empty
+    pub(crate) fn bounds(&self) -> Option<Rect> {
+        points_bounds(&self.points)
+    }
+
+    /// SVG path data, e.g. `M 0.00 0.00 L 10.00 0.00`. Polylines ending on
+    /// their first point are closed with `Z`.
+    #[must_use]
+    pub fn to_svg_path_data(&self) -> String {
+        let closed = self.point_count() > 2 && self.first_point() == self.last_point();
+
+        let points = match self.points.split_last() {
+            Some((_, rest)) if closed => rest,
+            _ => &self.points[..],
+        };
+
+        let mut data = String::new();
+
+        for (i, point) in points.iter().enumerate() {
+            let command = if i == 0 { "M" } else { " L" };
+            // Writing to a String cannot fail
+            let _ = write!(
+                data,
+                "{command} {} {}",
+                svg_number(point.x),
+                svg_number(point.y)
+            );
+        }
+
+        if closed {
+            data.push_str(" Z");
+        }
+
+        data
+    }
+}
+
+fn distance_to_segment(point: Vec2, start: Vec2, end: Vec2) -> f32 {
+    let segment = end - start;
+    let length_squared = segment.length_squared();
+
+    if length_squared == 0.0 {
+        return point.distance(start);
+    }
+
+    // Projection of the point on the segment, clamped to its ends
+    let t = ((point - start).dot(segment) / length_squared).clamp(0.0, 1.0);
+
+    point.distance(start + segment * t)
+}
+
+/// Extra tolerance of [`StrokeHitTester`] around the stroke, in pixels
+const HIT_TOLERANCE: f32 = 2.0;
+
+/// Tells whether a point is on a completed stroke, the basis for picking
+/// strokes with the pointer
+#[derive(SystemParam)]
+pub(crate) struct StrokeHitTester<'w, 's> {
+    strokes: Query<
+        'w,
+        's,
+        (
+            Entity,
+            &'static Polyline,
+            &'static Stroke,
+            &'static GlobalTransform,
+        ),
+        With<Completed>,
+    >,
+    projection_q: Query<'w, 's, &'static OrthographicProjection, With<MainCamera>>,
+}
+
+impl StrokeHitTester<'_, '_> {
+    /// Whether `point`, in world coordinates, is within half the line width
+    /// (plus a small tolerance) of the stroke
+    #[must_use]
+    pub(crate) fn hit_test(&self, entity: Entity, point: Vec2) -> bool {
+        let Ok((_, polyline, stroke, transform)) = self.strokes.get(entity) else {
+            return false;
+        };
+
+        // World units per pixel, more when zoomed out
+        let scale = self
+            .projection_q
+            .get_single()
+            .map_or(1.0, |projection| projection.scale);
+
+        is_hit(polyline, stroke, transform, point, HIT_TOLERANCE * scale)
+    }
+
+    /// The stroke drawn above the others at `point`, if any
+    #[must_use]
+    pub(crate) fn topmost(&self, point: Vec2) -> Option<Entity> {
+        self.strokes
+            .iter()
+            .filter(|(entity, ..)| self.hit_test(*entity, point))
+            .max_by(|(.., a), (.., b)| a.translation().z.total_cmp(&b.translation().z))
+            .map(|(entity, ..)| entity)
+    }
+}
+
+/// `tolerance` is in world units
+fn is_hit(
+    polyline: &Polyline,
+    stroke: &Stroke,
+    transform: &GlobalTransform,
+    point: Vec2,
+    tolerance: f32,
+) -> bool {
+    let local_point = transform
+        .affine()
+        .inverse()
+        .transform_point3(point.extend(0.0))
+        .truncate();
+
+    let max_distance = stroke.options.line_width / 2.0 + tolerance;
+
+    polyline
+        .distance_to(local_point)
+        .is_some_and(|distance| distance <= max_distance)
+}
+
+fn points_bounds(points: &[Vec2]) -> Option<Rect> {
+    let (first, rest) = points.split_first()?;
+
+    let rect = rest
+        .iter()
+        .fold(Rect::from_corners(*first, *first), |rect, point| {
+            rect.union_point(*point)
+        });
+
+    Some(rect)
+}
+
+/// SVG `transform` attribute moving the bounding box of `points` to the
+/// origin. With `flip_y`, the y axis is flipped too: y points up in the
+/// world, but down in SVG.
+#[must_use]
+pub fn svg_transform(points: &[Vec2], flip_y: bool) -> String {
+    let Some(bounds) = points_bounds(points) else {
+        return String::new();
+    };
+
+    let x = svg_number(-bounds.min.x);
+
+    if flip_y {
+        format!("matrix(1 0 0 -1 {x} {})", svg_number(bounds.max.y))
+    } else {
+        format!("translate({x} {})", svg_number(-bounds.min.y))
+    }
+}
+
+/// Numbers in SVG output have two decimals. `-0.00` is written `0.00`, the
+/// origin must not depend on how it was computed.
+fn svg_number(value: f32) -> String {
+    let number = format!("{value:.2}");
+
+    if number == "-0.00" {
+        "0.00".to_owned()
+    } else {
+        number
+    }
+}
+
+/// Metadata of a completed stroke
+#[derive(Debug, Clone, Copy, Component, Default, Reflect)]
+#[reflect(Component)]
+#[cfg_attr(feature = "lavagna_serde", derive(Serialize, Deserialize))]
+pub(crate) struct StrokeMeta {
+    /// Seconds elapsed since startup when the stroke was completed
+    created_at: f32,
+}
+
+impl StrokeMeta {
+    pub(crate) fn created_at(&self) -> f32 {
+        self.created_at
+    }
+}
+
+/// Everything needed to spawn a completed stroke
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "lavagna_serde", derive(Serialize, Deserialize))]
+pub(crate) struct StrokeRecord {
+    polyline: Polyline,
+    chalk: Chalk,
+    meta: StrokeMeta,
+}
+
+/// Points from `[x, y]` pairs, as found in JSON or other serialized formats
+impl From<Vec<[f32; 2]>> for Polyline {
+    fn from(points: Vec<[f32; 2]>) -> Self {
+        Self {
+            points: points.into_iter().map(Vec2::from).collect(),
+        }
+    }
+}
+
+/// `[x, y]` pairs of the points, the inverse of the conversion above
+impl From<Polyline> for Vec<[f32; 2]> {
+    fn from(polyline: Polyline) -> Self {
+        polyline.points.into_iter().map(Vec2::to_array).collect()
+    }
+}
+
+/// Points from `(x, y)` tuples, handy for literals
+impl From<&[(f32, f32)]> for Polyline {
+    fn from(points: &[(f32, f32)]) -> Self {
+        Self {
+            points: points.iter().map(|&point| Vec2::from(point)).collect(),
+        }
+    }
+}
+
+/// Closed outline of the rectangle
+impl From<Rect> for Polyline {
+    fn from(rect: Rect) -> Self {
+        Self {
+            points: vec![
+                rect.min,
+                Vec2::new(rect.max.x, rect.min.y),
+                rect.max,
+                Vec2::new(rect.min.x, rect.max.y),
+                rect.min,
+            ],
+        }
+    }
+}
+
+/// A path made of dashes along the polyline. Without a positive dash and
+/// period, the dashes could not advance and the path is solid.
+pub(crate) fn dashed_path(polyline: &Polyline, dash: f32, gap: f32) -> Path {
+    if dash <= 0.0 || dash + gap <= 0.0 {
+        return Path::from(polyline);
+    }
+
+    let mut path_builder = PathBuilder::new();
+
+    let length = polyline.length();
+    let mut s = 0.0;
+
+    while s < length {
+        path_builder.move_to(polyline.point_at_arc_length(s));
+        path_builder.line_to(polyline.point_at_arc_length((s + dash).min(length)));
+        s += dash + gap;
+    }
+
+    path_builder.build()
+}
+
 impl From<&Polyline> for Path {
     fn from(polyline: &Polyline) -> Self {
         let mut path_builder = PathBuilder::new();
@@ -149,34 +914,936 @@ impl From<&Polyline> for Path {
     }
 }
 
-fn despawn_all_completed_lines(commands: &mut Commands, lines: &Query<Entity, With<Completed>>) {
-    for line in lines.iter() {
-        commands.entity(line).despawn();
-    }
+/// What [`History::undo`] undid. Only strokes are undone on the peers too,
+/// flips, moves and clears of this chalk are never sent to them.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Undone {
+    Stroke,
+    Polylines,
+    Cleared,
 }
 
-#[derive(Event)]
-pub(crate) struct ClearEvent(bool);
+/// Something undone at once
+#[derive(Debug)]
+enum HistoryRecord {
+    /// The chunks of a stroke
+    Stroke(Vec<Entity>),
+    /// The polylines of the completed strokes, before they were all flipped
+    /// or moved
+    Polylines(Vec<(Entity, Polyline)>),
+    /// Completed strokes, before the blackboard was cleared or a stroke was
+    /// deleted
+    Cleared(Vec<StrokeRecord>),
+}
 
-impl ClearEvent {
+/// Strokes completed by a chalk, oldest first. A single stroke can be made of
+/// many chunks (see [`ChunkThreshold`]), they are undone together.
+#[derive(Debug, Component, Default)]
+pub(crate) struct History {
+    records: Vec<HistoryRecord>,
+    drawing: bool,
+}
+
+impl History {
+    fn push(&mut self, chunk: Entity) {
+        match self.records.last_mut() {
+            Some(HistoryRecord::Stroke(stroke)) if self.drawing => stroke.push(chunk),
+            _ => self.records.push(HistoryRecord::Stroke(vec![chunk])),
+        }
+
+        self.drawing = true;
+    }
+
+    pub(crate) fn push_polylines(&mut self, polylines: Vec<(Entity, Polyline)>) {
+        self.records.push(HistoryRecord::Polylines(polylines));
+        self.drawing = false;
+    }
+
+    fn push_cleared(&mut self, strokes: Vec<StrokeRecord>) {
+        self.records.push(HistoryRecord::Cleared(strokes));
+        self.drawing = false;
+    }
+
+    /// A completed stroke deleted on its own, undo spawns it again
+    pub(crate) fn push_deleted(&mut self, polyline: Polyline, chalk: Chalk, meta: StrokeMeta) {
+        self.push_cleared(vec![StrokeRecord {
+            polyline,
+            chalk,
+            meta,
+        }]);
+    }
+
+    fn end_stroke(&mut self) {
+        self.drawing = false;
+    }
+
+    pub(crate) fn clear(&mut self) {
+        self.records.clear();
+        self.drawing = false;
+    }
+
+    /// Despawn the last completed stroke, or restore the strokes as they
+    /// were before the last flip, move or clear. Returns what was undone.
+    pub(crate) fn undo(&mut self, commands: &mut Commands) -> Option<Undone> {
+        self.drawing = false;
+
+        match self.records.pop()? {
+            HistoryRecord::Stroke(stroke) => {
+                for chunk in stroke {
+                    if let Some(mut entity) = commands.get_entity(chunk) {
+                        entity.despawn();
+                    }
+                }
+                Some(Undone::Stroke)
+            }
+            HistoryRecord::Polylines(polylines) => {
+                for (stroke, polyline) in polylines {
+                    if let Some(mut entity) = commands.get_entity(stroke) {
+                        entity.insert((Path::from(&polyline), polyline));
+                    }
+                }
+                Some(Undone::Polylines)
+            }
+            HistoryRecord::Cleared(strokes) => {
+                for record in strokes {
+                    spawn_stroke(commands, record);
+                }
+                Some(Undone::Cleared)
+            }
+        }
+    }
+}
+
+fn despawn_all_completed_lines(
+    commands: &mut Commands,
+    lines: &Query<(Entity, &Polyline, &Chalk, &StrokeMeta), With<Completed>>,
+) {
+    for (line, _, _, _) in lines.iter() {
+        commands.entity(line).despawn();
+    }
+}
+
+fn despawn_completed_lines_in_region(
+    commands: &mut Commands,
+    lines: &Query<(Entity, &Polyline, &Chalk, &StrokeMeta), With<Completed>>,
+    region: Rect,
+    config: &EraserConfig,
+    revivable: bool,
+) {
+    for (line, polyline, chalk, _) in lines.iter() {
+        let Some(bounds) = polyline.bounds() else {
+            continue;
+        };
+
+        if bounds
+            .inflate(chalk.line_width / 2.0)
+            .intersect(region)
+            .is_empty()
+        {
+            continue;
+        }
+
+        erase_stroke(commands, line, config, revivable);
+    }
+}
+
+/// Despawn a completed stroke, or turn it into a ghost if enabled. Only
+/// `revivable` ghosts are brought back by undo, see [`EraserGhost`].
+pub(crate) fn erase_stroke(
+    commands: &mut Commands,
+    stroke: Entity,
+    config: &EraserConfig,
+    revivable: bool,
+) {
+    if config.show_ghost {
+        let ghost = EraserGhost {
+            timer: Timer::new(config.ghost_duration, TimerMode::Once),
+            revivable,
+        };
+        commands.entity(stroke).remove::<Completed>().insert(ghost);
+    } else {
+        commands.entity(stroke).despawn();
+    }
+}
+
+/// Alpha of erased strokes while they are shown as ghosts
+const GHOST_ALPHA: f32 = 0.15;
+
+/// Erased strokes can stay visible for a moment, very faint, to show what
+/// was erased. Until they disappear, undo brings them back.
+#[derive(Debug, Resource, Clone, Copy)]
+pub(crate) struct EraserConfig {
+    show_ghost: bool,
+    ghost_duration: Duration,
+}
+
+impl Default for EraserConfig {
+    fn default() -> Self {
+        Self {
+            show_ghost: true,
+            ghost_duration: Duration::from_secs(1),
+        }
+    }
+}
+
+/// An erased stroke, despawned when the timer expires. Not being
+/// [`Completed`], it is neither saved nor counted, nor erased again.
+#[derive(Debug, Component)]
+pub(crate) struct EraserGhost {
+    timer: Timer,
+    /// Undo brings it back: erased here, not by a peer or a replay, and not
+    /// recorded in the history either
+    revivable: bool,
+}
+
+impl EraserGhost {
+    pub(crate) fn is_revivable(&self) -> bool {
+        self.revivable
+    }
+}
+
+fn tick_eraser_ghosts(
+    mut commands: Commands,
+    mut ghost_q: Query<(Entity, &mut EraserGhost, &mut Stroke)>,
+    time: Res<Time>,
+) {
+    for (entity, mut ghost, mut stroke) in &mut ghost_q {
+        if ghost.is_added() {
+            stroke.color.set_alpha(GHOST_ALPHA);
+        }
+
+        if ghost.timer.tick(time.delta()).finished() {
+            commands.entity(entity).despawn();
+        }
+    }
+}
+
+/// Undo brings the strokes erased here back, while they are ghosts. Undoing
+/// the last stroke is skipped meanwhile, see `handle_undo_event`. Strokes
+/// erased by a peer or a replay stay erased.
+fn unerase_ghosts(
+    mut commands: Commands,
+    mut undo_events: EventReader<UndoEvent>,
+    mut ghost_q: Query<(Entity, &mut Stroke, &Chalk, &EraserGhost)>,
+) {
+    if undo_events.read().count() == 0 {
+        return;
+    }
+
+    for (entity, mut stroke, chalk, ghost) in &mut ghost_q {
+        if !ghost.revivable {
+            continue;
+        }
+
+        stroke.color = chalk.color.into();
+        commands
+            .entity(entity)
+            .remove::<EraserGhost>()
+            .insert(Completed);
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum FlipAxis {
+    /// Mirror left and right
+    Horizontal,
+    /// Mirror top and bottom
+    Vertical,
+}
+
+/// Mirror all the completed strokes across the world origin, the blackboard
+/// having no edges. Local only, peers keep their strokes as they are.
+#[derive(Event)]
+pub(crate) struct FlipCanvasEvent {
+    pub(crate) axis: FlipAxis,
+}
+
+fn handle_flip_canvas_event(
+    mut events: EventReader<FlipCanvasEvent>,
+    mut lines: Query<(Entity, &mut Polyline, &mut Path), With<Completed>>,
+    mut history_q: Query<&mut History, With<LocalChalkFlag>>,
+) {
+    for event in events.read() {
+        let mut previous = Vec::new();
+
+        for (entity, mut polyline, mut path) in &mut lines {
+            previous.push((entity, polyline.clone()));
+
+            for point in &mut polyline.points {
+                match event.axis {
+                    FlipAxis::Horizontal => point.x = -point.x,
+                    FlipAxis::Vertical => point.y = -point.y,
+                }
+            }
+
+            *path = Path::from(&*polyline);
+        }
+
+        // A single undo restores the whole canvas
+        if let Ok(mut history) = history_q.get_single_mut() {
+            history.push_polylines(previous);
+        }
+    }
+}
+
+/// Move all the completed strokes by `delta`, in world units. Unlike panning,
+/// the ink moves and the camera stays. Local only, like [`FlipCanvasEvent`].
+#[derive(Event)]
+pub(crate) struct TranslateCanvasEvent {
+    pub(crate) delta: Vec2,
+}
+
+fn handle_translate_canvas_event(
+    mut events: EventReader<TranslateCanvasEvent>,
+    mut lines: Query<(Entity, &mut Polyline, &mut Path), With<Completed>>,
+    mut history_q: Query<&mut History, With<LocalChalkFlag>>,
+) {
+    for event in events.read() {
+        let mut previous = Vec::new();
+
+        for (entity, mut polyline, mut path) in &mut lines {
+            previous.push((entity, polyline.clone()));
+            polyline.translate(event.delta);
+            *path = Path::from(&*polyline);
+        }
+
+        if let Ok(mut history) = history_q.get_single_mut() {
+            history.push_polylines(previous);
+        }
+    }
+}
+
+/// What is removed by a [`ClearEvent`]
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "lavagna_serde", derive(Serialize, Deserialize))]
+pub(crate) enum ClearTarget {
+    /// The whole blackboard
+    All,
+    /// Strokes whose bounds overlap this rectangle, in world coordinates
+    Region(Rect),
+}
+
+#[derive(Event)]
+pub(crate) struct ClearEvent {
+    target: ClearTarget,
+    forward: bool,
+}
+
+impl ClearEvent {
     pub(crate) fn new() -> Self {
-        Self(true)
+        Self {
+            target: ClearTarget::All,
+            forward: true,
+        }
     }
-    pub(crate) fn local_only() -> Self {
-        Self(false)
+    pub(crate) fn region(rect: Rect) -> Self {
+        Self {
+            target: ClearTarget::Region(rect),
+            forward: true,
+        }
+    }
+    pub(crate) fn local_only(target: ClearTarget) -> Self {
+        Self {
+            target,
+            forward: false,
+        }
+    }
+    pub(crate) fn target(&self) -> ClearTarget {
+        self.target
     }
     pub(crate) fn must_be_forwarded(&self) -> bool {
-        self.0
+        self.forward
     }
 }
 
+/// Above this many strokes, keeping a clear undoable takes a lot of memory
+const LARGE_CLEAR_THRESHOLD: usize = 1000;
+
+/// Clearing the whole blackboard can be undone, but only by who cleared it:
+/// strokes cleared by a peer or by a replay are gone
 fn handle_clear_event(
     mut events: EventReader<ClearEvent>,
-    lines: Query<Entity, With<Completed>>,
+    lines: Query<(Entity, &Polyline, &Chalk, &StrokeMeta), With<Completed>>,
+    mut histories: Query<(&mut History, Has<LocalChalkFlag>)>,
     mut commands: Commands,
+    config: Res<EraserConfig>,
 ) {
-    let clear = events.read().count() > 0;
-    if clear {
-        despawn_all_completed_lines(&mut commands, &lines);
+    for event in events.read() {
+        match event.target {
+            ClearTarget::All => {
+                let mut cleared: Vec<StrokeRecord> = lines
+                    .iter()
+                    .map(|(_, polyline, chalk, meta)| StrokeRecord {
+                        polyline: polyline.clone(),
+                        chalk: *chalk,
+                        meta: *meta,
+                    })
+                    .collect();
+
+                despawn_all_completed_lines(&mut commands, &lines);
+
+                for (mut history, local) in &mut histories {
+                    history.clear();
+
+                    if local && event.must_be_forwarded() && !cleared.is_empty() {
+                        if cleared.len() > LARGE_CLEAR_THRESHOLD {
+                            log::warn!(
+                                "keeping {} cleared strokes in memory, to undo the clear",
+                                cleared.len()
+                            );
+                        }
+
+                        history.push_cleared(std::mem::take(&mut cleared));
+                    }
+                }
+            }
+            // Histories may still refer to the despawned strokes, undo
+            // skips them. Only regions erased here can be brought back.
+            ClearTarget::Region(region) => {
+                despawn_completed_lines_in_region(
+                    &mut commands,
+                    &lines,
+                    region,
+                    &config,
+                    event.must_be_forwarded(),
+                );
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+
+    fn polyline(points: &[(f32, f32)]) -> Polyline {
+        Polyline::from(points)
+    }
+
+    fn assert_close(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-5, "{actual} != {expected}");
+    }
+
+    fn assert_point(actual: Vec2, expected: (f32, f32)) {
+        let expected = Vec2::from(expected);
+        assert!(actual.abs_diff_eq(expected, 1e-5), "{actual} != {expected}");
+    }
+
+    #[test]
+    fn empty_polyline_has_no_length() {
+        let empty = Polyline::default();
+
+        assert_close(empty.length(), 0.0);
+        assert_point(empty.point_at_arc_length(1.0), (0.0, 0.0));
+    }
+
+    #[test]
+    fn single_point_has_no_length() {
+        let point = polyline(&[(3.0, 4.0)]);
+
+        assert_close(point.length(), 0.0);
+        assert_point(point.point_at_arc_length(0.0), (3.0, 4.0));
+        assert_point(point.point_at_arc_length(5.0), (3.0, 4.0));
+    }
+
+    #[test]
+    fn length_sums_the_segments() {
+        let corner = polyline(&[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)]);
+
+        assert_close(corner.length(), 2.0);
+    }
+
+    #[test]
+    fn arc_length_zero_is_the_first_point() {
+        let corner = polyline(&[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)]);
+
+        assert_point(corner.point_at_arc_length(0.0), (0.0, 0.0));
+        assert_point(corner.point_at_arc_length(-1.0), (0.0, 0.0));
+    }
+
+    #[test]
+    fn full_arc_length_is_the_last_point() {
+        let corner = polyline(&[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)]);
+
+        assert_point(corner.point_at_arc_length(corner.length()), (1.0, 1.0));
+    }
+
+    #[test]
+    fn arc_length_past_the_end_is_the_last_point() {
+        let corner = polyline(&[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)]);
+
+        assert_point(corner.point_at_arc_length(10.0), (1.0, 1.0));
+    }
+
+    #[test]
+    fn arc_length_is_interpolated_along_segments() {
+        let corner = polyline(&[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)]);
+
+        assert_point(corner.point_at_arc_length(0.5), (0.5, 0.0));
+        assert_point(corner.point_at_arc_length(1.0), (1.0, 0.0));
+        assert_point(corner.point_at_arc_length(1.25), (1.0, 0.25));
+    }
+
+    #[test]
+    fn points_outside_the_canvas_are_clamped() {
+        let bounds = CanvasBounds::default();
+        let mut polyline = Polyline::default();
+
+        let chalk = Chalk {
+            x: 1.0e9,
+            y: f32::NEG_INFINITY,
+            ..default()
+        };
+        add_point(&mut polyline, &chalk, &bounds);
+
+        assert_eq!(
+            polyline.last_point(),
+            Some(Vec2::new(100_000.0, -100_000.0))
+        );
+    }
+
+    #[test]
+    fn points_inside_the_canvas_are_kept() {
+        let bounds = CanvasBounds::default();
+        let mut polyline = Polyline::default();
+
+        let chalk = Chalk {
+            x: 12.5,
+            y: -3.0,
+            ..default()
+        };
+        add_point(&mut polyline, &chalk, &bounds);
+
+        assert_eq!(polyline.last_point(), Some(Vec2::new(12.5, -3.0)));
+    }
+
+    fn points(polyline: &Polyline) -> Vec<[f32; 2]> {
+        polyline.clone().into()
+    }
+
+    #[test]
+    fn reversing_empty_polyline_gives_empty() {
+        assert!(Polyline::default().reverse().is_empty());
+    }
+
+    #[test]
+    fn reversing_single_point_gives_the_same_point() {
+        let point = polyline(&[(1.0, 2.0)]);
+
+        assert_eq!(points(&point.reverse()), points(&point));
+    }
+
+    #[test]
+    fn reverse_swaps_the_point_order() {
+        let line = polyline(&[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)]);
+
+        assert_eq!(
+            points(&line.reverse()),
+            vec![[1.0, 1.0], [1.0, 0.0], [0.0, 0.0]]
+        );
+    }
+
+    #[test]
+    fn reversing_twice_gives_the_original() {
+        let line = polyline(&[(0.0, 0.0), (1.0, 0.0), (1.0, 1.0)]);
+
+        assert_eq!(points(&line.reverse().reverse()), points(&line));
+
+        let mut in_place = line.clone();
+        in_place.reversed_in_place();
+        in_place.reversed_in_place();
+        assert_eq!(points(&in_place), points(&line));
+    }
+
+    #[test]
+    fn completed_path_is_spawned_as_a_completed_stroke() {
+        let mut world = World::new();
+        world.init_resource::<Time>();
+
+        let (entity, pending_cleared) =
+            world.run_system_once(|mut commands: Commands, time: Res<Time>| {
+                let mut pending = polyline(&[(0.0, 0.0), (1.0, 0.0)]);
+                let entity =
+                    complete_pending_path(&mut pending, &mut commands, &Chalk::default(), &time);
+                (entity, pending.is_empty())
+            });
+
+        assert!(pending_cleared);
+        assert!(world.get::<Completed>(entity).is_some());
+        assert_eq!(
+            world.get::<Polyline>(entity).map(Polyline::point_count),
+            Some(2)
+        );
+    }
+
+    fn path_events(path: &Path) -> usize {
+        path.0.iter().count()
+    }
+
+    #[test]
+    fn dashes_are_spaced_along_the_polyline() {
+        let line = polyline(&[(0.0, 0.0), (10.0, 0.0)]);
+
+        // Dashes start at 0 and 5, each one is begin, line and end
+        assert_eq!(path_events(&dashed_path(&line, 2.0, 3.0)), 6);
+    }
+
+    #[test]
+    fn dashes_which_cannot_advance_give_a_solid_path() {
+        let line = polyline(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0)]);
+        let solid = path_events(&Path::from(&line));
+
+        assert_eq!(path_events(&dashed_path(&line, 0.0, 3.0)), solid);
+        assert_eq!(path_events(&dashed_path(&line, -1.0, 3.0)), solid);
+        assert_eq!(path_events(&dashed_path(&line, 2.0, -2.0)), solid);
+    }
+
+    #[test]
+    fn strokes_older_than_the_z_origin_stay_in_front_of_the_background() {
+        let mut world = World::new();
+        world.insert_resource(ZOrigin(100.0));
+        let stroke = world
+            .spawn((Transform::from_xyz(0.0, 0.0, 10.0), Completed))
+            .id();
+
+        world.run_system_once(separate_stroke_depths);
+
+        let z = world.get::<Transform>(stroke).unwrap().translation.z;
+        assert!(z >= 0.0, "{z}");
+    }
+
+    #[test]
+    fn undo_brings_back_only_strokes_erased_here() {
+        let mut world = World::new();
+        world.init_resource::<Events<UndoEvent>>();
+
+        let ghost = |revivable| {
+            (
+                Stroke::new(Color::WHITE, 1.0),
+                Chalk::default(),
+                EraserGhost {
+                    timer: Timer::from_seconds(1.0, TimerMode::Once),
+                    revivable,
+                },
+            )
+        };
+        let local = world.spawn(ghost(true)).id();
+        let remote = world.spawn(ghost(false)).id();
+
+        world.send_event(UndoEvent);
+        world.run_system_once(unerase_ghosts);
+
+        assert!(world.get::<Completed>(local).is_some());
+        assert!(world.get::<EraserGhost>(remote).is_some());
+        assert!(world.get::<Completed>(remote).is_none());
+    }
+
+    #[test]
+    fn only_remote_chalks_expire() {
+        let mut world = World::new();
+        world.init_resource::<Time>();
+
+        let pressed = Chalk {
+            pressed: true,
+            ..Chalk::default()
+        };
+        let stroke = || {
+            (
+                pressed,
+                polyline(&[(0.0, 0.0)]),
+                PendingTimeout::default(),
+                Pending,
+            )
+        };
+        let remote = world.spawn(stroke()).id();
+        let local = world.spawn((stroke(), LocalChalkFlag)).id();
+        let touch = world.spawn((stroke(), TouchChalk(Some(1)))).id();
+
+        world
+            .resource_mut::<Time>()
+            .advance_by(Duration::from_secs(6));
+        world.run_system_once(expire_pending_strokes);
+
+        let remote = world.get::<Chalk>(remote).unwrap();
+        assert!(!remote.pressed);
+        assert!(remote.just_released);
+        assert!(world.get::<Chalk>(local).unwrap().pressed);
+        assert!(world.get::<Chalk>(touch).unwrap().pressed);
+    }
+
+    #[test]
+    fn open_polyline_svg_path_data() {
+        let line = polyline(&[(0.0, 0.0), (10.0, 0.0), (10.0, 5.5)]);
+
+        assert_eq!(
+            line.to_svg_path_data(),
+            "M 0.00 0.00 L 10.00 0.00 L 10.00 5.50"
+        );
+    }
+
+    #[test]
+    fn closed_polyline_svg_path_data() {
+        let square = polyline(&[(0.0, 0.0), (10.0, 0.0), (10.0, 10.0), (0.0, 0.0)]);
+
+        assert_eq!(
+            square.to_svg_path_data(),
+            "M 0.00 0.00 L 10.00 0.00 L 10.00 10.00 Z"
+        );
+    }
+
+    #[test]
+    fn empty_polyline_svg_path_data() {
+        assert_eq!(Polyline::default().to_svg_path_data(), "");
+    }
+
+    #[test]
+    fn svg_has_no_negative_zero() {
+        let point = polyline(&[(-0.0, -0.001)]);
+
+        assert_eq!(point.to_svg_path_data(), "M 0.00 0.00");
+        assert_eq!(svg_transform(&point.points, false), "translate(0.00 0.00)");
+    }
+
+    #[test]
+    fn svg_transform_moves_the_bounds_to_the_origin() {
+        let points = [Vec2::new(2.0, 3.0), Vec2::new(10.0, 5.0)];
+
+        assert_eq!(svg_transform(&points, false), "translate(-2.00 -3.00)");
+        assert_eq!(svg_transform(&points, true), "matrix(1 0 0 -1 -2.00 5.00)");
+        assert_eq!(svg_transform(&[], false), "");
+    }
+
+    #[test]
+    fn distance_to_zero_length_segment_is_to_its_point() {
+        let start = Vec2::new(1.0, 1.0);
+
+        assert_close(distance_to_segment(Vec2::new(4.0, 5.0), start, start), 5.0);
+    }
+
+    #[test]
+    fn points_projecting_past_the_ends_measure_to_the_ends() {
+        let start = Vec2::ZERO;
+        let end = Vec2::new(10.0, 0.0);
+
+        assert_close(distance_to_segment(Vec2::new(-3.0, 4.0), start, end), 5.0);
+        assert_close(distance_to_segment(Vec2::new(13.0, -4.0), start, end), 5.0);
+    }
+
+    #[test]
+    fn points_projecting_inside_measure_to_the_segment() {
+        let start = Vec2::ZERO;
+        let end = Vec2::new(10.0, 0.0);
+
+        assert_close(distance_to_segment(Vec2::new(5.0, 2.0), start, end), 2.0);
+        assert_close(distance_to_segment(Vec2::new(7.0, 0.0), start, end), 0.0);
+    }
+
+    #[test]
+    fn hits_reach_half_the_width_plus_the_tolerance() {
+        let line = polyline(&[(0.0, 0.0), (10.0, 0.0)]);
+        let stroke = Stroke::new(Color::WHITE, 4.0);
+        let hit = |y| {
+            is_hit(
+                &line,
+                &stroke,
+                &GlobalTransform::IDENTITY,
+                Vec2::new(5.0, y),
+                HIT_TOLERANCE,
+            )
+        };
+
+        assert!(hit(2.0));
+        assert!(hit(2.0 + HIT_TOLERANCE));
+        assert!(!hit(2.0 + HIT_TOLERANCE + 0.01));
+    }
+
+    #[test]
+    fn hit_tolerance_stays_in_pixels_when_zoomed_out() {
+        let mut world = World::new();
+        world.spawn((
+            OrthographicProjection {
+                scale: 4.0,
+                ..default()
+            },
+            MainCamera,
+        ));
+        let stroke = world
+            .spawn((
+                polyline(&[(0.0, 0.0), (10.0, 0.0)]),
+                Stroke::new(Color::WHITE, 4.0),
+                GlobalTransform::IDENTITY,
+                Completed,
+            ))
+            .id();
+
+        let hit = |world: &mut World, y| {
+            world.run_system_once(move |tester: StrokeHitTester| {
+                tester.hit_test(stroke, Vec2::new(5.0, y))
+            })
+        };
+
+        assert!(hit(&mut world, 2.0 + 4.0 * HIT_TOLERANCE));
+        assert!(!hit(&mut world, 2.0 + 4.0 * HIT_TOLERANCE + 0.01));
+    }
+
+    #[test]
+    fn coordinate_pairs_round_trip() {
+        let pairs = vec![[0.0, 1.0], [2.0, 3.0]];
+
+        assert_eq!(Vec::<[f32; 2]>::from(Polyline::from(pairs.clone())), pairs);
+    }
+
+    #[test]
+    fn flat_coordinates_need_an_even_length() {
+        let line = Polyline::from_flat_coords(&[0.0, 1.0, 2.0, 3.0]).unwrap();
+
+        assert_eq!(points(&line), vec![[0.0, 1.0], [2.0, 3.0]]);
+        assert!(Polyline::from_flat_coords(&[0.0, 1.0, 2.0]).is_none());
+    }
+
+    #[test]
+    fn rectangle_outline_is_closed() {
+        let outline = Polyline::from(Rect::new(0.0, 0.0, 2.0, 1.0));
+
+        assert_eq!(
+            points(&outline),
+            vec![[0.0, 0.0], [2.0, 0.0], [2.0, 1.0], [0.0, 1.0], [0.0, 0.0]]
+        );
+    }
+
+    #[test]
+    fn repeated_points_are_skipped() {
+        let bounds = CanvasBounds::default();
+        let chalk = Chalk {
+            x: 1.0,
+            y: 2.0,
+            ..Chalk::default()
+        };
+        let mut line = Polyline::default();
+
+        add_point(&mut line, &chalk, &bounds);
+        add_point(&mut line, &chalk, &bounds);
+
+        assert_eq!(points(&line), vec![[1.0, 2.0]]);
+    }
+
+    /// Run `update` once with a chalk pressed at `(x, 0)`, returning the
+    /// points of the pending stroke
+    fn update_pressed_at(world: &mut World, x: f32) -> Vec<[f32; 2]> {
+        let mut pending = world.query_filtered::<(&mut Chalk, &Polyline), With<Pending>>();
+
+        let (mut chalk, _) = pending.single_mut(world);
+        chalk.x = x;
+        chalk.updated = true;
+
+        world.run_system_once(update);
+
+        points(pending.single(world).1)
+    }
+
+    #[test]
+    fn fresh_chunk_starts_at_the_last_point_once() {
+        let mut world = World::new();
+        world.init_resource::<Time>();
+        world.init_resource::<CanvasBounds>();
+        world.init_resource::<NoiseFilterConfig>();
+        world.insert_resource(ChunkThreshold(3));
+        world.spawn(make_chalk(Chalk {
+            pressed: true,
+            ..Chalk::default()
+        }));
+
+        assert_eq!(update_pressed_at(&mut world, 0.0), vec![[0.0, 0.0]]);
+        assert_eq!(update_pressed_at(&mut world, 1.0).len(), 2);
+
+        // The third point completes the chunk, and starts the next one
+        assert_eq!(update_pressed_at(&mut world, 2.0), vec![[2.0, 0.0]]);
+        assert_eq!(
+            world
+                .query_filtered::<(), With<Completed>>()
+                .iter(&world)
+                .count(),
+            1
+        );
+
+        // The chalk did not move, the new chunk must not repeat its point
+        assert_eq!(update_pressed_at(&mut world, 2.0), vec![[2.0, 0.0]]);
+    }
+
+    #[test]
+    fn consecutive_duplicates_are_removed() {
+        let mut line = polyline(&[
+            (0.0, 0.0),
+            (0.0, 0.0),
+            (1.0, 0.0),
+            (1.0, 0.0),
+            (1.0, 0.0),
+            (2.0, 0.0),
+        ]);
+
+        line.deduplicate_consecutive();
+
+        assert_eq!(points(&line), vec![[0.0, 0.0], [1.0, 0.0], [2.0, 0.0]]);
+    }
+
+    #[test]
+    fn points_closer_than_epsilon_are_removed() {
+        let gap = [(0.0, 0.0), (0.5, 0.0), (2.0, 0.0)];
+
+        let mut below_gap = polyline(&gap);
+        below_gap.deduplicate_consecutive_epsilon(0.49);
+        assert_eq!(points(&below_gap).len(), 3);
+
+        let mut above_gap = polyline(&gap);
+        above_gap.deduplicate_consecutive_epsilon(0.51);
+        assert_eq!(points(&above_gap), vec![[0.0, 0.0], [2.0, 0.0]]);
+    }
+
+    #[test]
+    fn undo_tells_what_was_undone() {
+        let mut world = World::new();
+        let undone = world.run_system_once(|mut commands: Commands| {
+            let mut history = History::default();
+            history.push(Entity::PLACEHOLDER);
+            history.end_stroke();
+            history.push_polylines(Vec::new());
+            history.push_cleared(Vec::new());
+
+            [
+                history.undo(&mut commands),
+                history.undo(&mut commands),
+                history.undo(&mut commands),
+                history.undo(&mut commands),
+            ]
+        });
+
+        assert_eq!(
+            undone,
+            [
+                Some(Undone::Cleared),
+                Some(Undone::Polylines),
+                Some(Undone::Stroke),
+                None
+            ]
+        );
+    }
+
+    #[test]
+    fn undoing_a_deletion_brings_the_stroke_back() {
+        let mut world = World::new();
+        world.run_system_once(|mut commands: Commands| {
+            let mut history = History::default();
+            history.push_deleted(
+                polyline(&[(0.0, 0.0), (1.0, 1.0)]),
+                Chalk::default(),
+                StrokeMeta::default(),
+            );
+
+            history.undo(&mut commands)
+        });
+
+        let mut strokes = world.query_filtered::<&Polyline, With<Completed>>();
+        assert_eq!(strokes.iter(&world).count(), 1);
     }
 }
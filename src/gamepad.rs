@@ -0,0 +1,177 @@
+#![allow(clippy::needless_pass_by_value)]
+
+use bevy::input::gamepad::{Gamepad, GamepadAxisType, GamepadButtonType, Gamepads};
+use bevy::prelude::*;
+
+use crate::drawing::CanvasBounds;
+use crate::local_chalk::{
+    handle_user_input, ChalkSource, ChangeColorEvent, GrowEvent, LocalChalk, ShrinkEvent,
+};
+use crate::AppMode;
+
+/// Draw with a gamepad, for example on a TV. The left stick moves the chalk,
+/// the right trigger presses it.
+pub(crate) struct GamepadDrawingPlugin;
+
+impl Plugin for GamepadDrawingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<GamepadConfig>()
+            .init_resource::<DeadZone>()
+            .init_resource::<GamepadCursor>()
+            // The mouse cursor moves the chalk too, the gamepad must win
+            .add_systems(Update, gamepad_cursor.after(handle_user_input))
+            .add_systems(Update, gamepad_shortcuts);
+
+        let mode = app
+            .world()
+            .get_resource::<AppMode>()
+            .copied()
+            .unwrap_or_default();
+
+        // Spectators move the cursor, but never press the chalk
+        if mode == AppMode::Drawing {
+            app.add_systems(Update, gamepad_press);
+        }
+    }
+}
+
+#[derive(Debug, Resource, Clone, Copy)]
+pub(crate) struct GamepadConfig {
+    /// Chalk speed with the stick fully tilted, in world units per second
+    /// (pixels, unless zoomed)
+    cursor_speed: f32,
+    draw: GamepadButtonType,
+    next_color: GamepadButtonType,
+    grow: GamepadButtonType,
+    shrink: GamepadButtonType,
+}
+
+impl Default for GamepadConfig {
+    fn default() -> Self {
+        Self {
+            cursor_speed: 400.0,
+            draw: GamepadButtonType::RightTrigger2,
+            next_color: GamepadButtonType::LeftTrigger2,
+            grow: GamepadButtonType::DPadUp,
+            shrink: GamepadButtonType::DPadDown,
+        }
+    }
+}
+
+/// Stick tilts below this are ignored, sticks rarely rest exactly at 0
+#[derive(Debug, Resource, Clone, Copy)]
+pub(crate) struct DeadZone(f32);
+
+impl Default for DeadZone {
+    fn default() -> Self {
+        Self(0.15)
+    }
+}
+
+/// Position of the gamepad cursor, in world coordinates. It starts from the
+/// chalk whenever the gamepad takes the chalk over from another source.
+#[derive(Debug, Resource, Default)]
+struct GamepadCursor(Vec2);
+
+/// Only the first connected gamepad draws
+fn first_gamepad(gamepads: &Gamepads) -> Option<Gamepad> {
+    gamepads.iter().next()
+}
+
+/// The stick sets the chalk velocity, not its position. Once it moves, the
+/// mouse leaves the chalk alone until the mouse cursor moves.
+#[allow(clippy::too_many_arguments)]
+fn gamepad_cursor(
+    gamepads: Res<Gamepads>,
+    axes: Res<Axis<GamepadAxis>>,
+    config: Res<GamepadConfig>,
+    dead_zone: Res<DeadZone>,
+    time: Res<Time>,
+    bounds: Res<CanvasBounds>,
+    mut cursor: ResMut<GamepadCursor>,
+    mut source: ResMut<ChalkSource>,
+    mut chalk: ResMut<LocalChalk>,
+) {
+    let Some(gamepad) = first_gamepad(&gamepads) else {
+        return;
+    };
+
+    let axis = |axis_type| {
+        axes.get(GamepadAxis::new(gamepad, axis_type))
+            .unwrap_or_default()
+    };
+
+    let stick = Vec2::new(
+        axis(GamepadAxisType::LeftStickX),
+        axis(GamepadAxisType::LeftStickY),
+    );
+
+    if stick.length() < dead_zone.0 {
+        return;
+    }
+
+    let chalk = &mut chalk.0;
+
+    if *source != ChalkSource::Gamepad {
+        *source = ChalkSource::Gamepad;
+        cursor.0 = Vec2::new(chalk.x, chalk.y);
+    }
+
+    let offset = stick * config.cursor_speed * time.delta_seconds();
+    cursor.0 = bounds.clamp(cursor.0 + offset);
+
+    let prev_chalk = *chalk;
+    chalk.x = cursor.0.x;
+    chalk.y = cursor.0.y;
+    chalk.updated = chalk.is_updated_from(&prev_chalk);
+}
+
+fn gamepad_press(
+    gamepads: Res<Gamepads>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+    config: Res<GamepadConfig>,
+    mut chalk: ResMut<LocalChalk>,
+) {
+    let Some(gamepad) = first_gamepad(&gamepads) else {
+        return;
+    };
+
+    let draw = GamepadButton::new(gamepad, config.draw);
+
+    if buttons.just_pressed(draw) {
+        chalk.0.pressed = true;
+        chalk.0.just_released = false;
+    }
+
+    if buttons.just_released(draw) {
+        chalk.0.pressed = false;
+        chalk.0.just_released = true;
+    }
+}
+
+fn gamepad_shortcuts(
+    gamepads: Res<Gamepads>,
+    buttons: Res<ButtonInput<GamepadButton>>,
+    config: Res<GamepadConfig>,
+    mut change_color_event: EventWriter<ChangeColorEvent>,
+    mut grow_event: EventWriter<GrowEvent>,
+    mut shrink_event: EventWriter<ShrinkEvent>,
+) {
+    let Some(gamepad) = first_gamepad(&gamepads) else {
+        return;
+    };
+
+    let just_pressed = |button_type| buttons.just_pressed(GamepadButton::new(gamepad, button_type));
+
+    if just_pressed(config.next_color) {
+        change_color_event.send(ChangeColorEvent);
+    }
+
+    if just_pressed(config.grow) {
+        grow_event.send(GrowEvent);
+    }
+
+    if just_pressed(config.shrink) {
+        shrink_event.send(ShrinkEvent);
+    }
+}
@@ -0,0 +1,89 @@
+#![allow(clippy::needless_pass_by_value)]
+
+use bevy::prelude::*;
+
+use crate::keybinding::KEY_BINDINGS;
+use crate::ui::default_font;
+
+/// All the keyboard shortcuts, shown over the drawing with F1 or '?'
+pub(crate) struct HelpOverlayPlugin;
+
+impl Plugin for HelpOverlayPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ToggleHelpEvent>()
+            .add_systems(Startup, startup_help_overlay)
+            .add_systems(Update, handle_toggle_help_event);
+    }
+}
+
+const FONT_SIZE: f32 = 18.0;
+
+#[derive(Event)]
+pub(crate) struct ToggleHelpEvent;
+
+#[derive(Component)]
+struct HelpOverlay;
+
+fn startup_help_overlay(mut commands: Commands, asset_server: Res<AssetServer>) {
+    let text_style = TextStyle {
+        font: default_font(&asset_server),
+        font_size: FONT_SIZE,
+        color: Color::WHITE,
+    };
+
+    let keys_width = KEY_BINDINGS.iter().map(|b| b.keys.len()).max().unwrap_or(0);
+    let description_width = KEY_BINDINGS
+        .iter()
+        .map(|b| b.description.len())
+        .max()
+        .unwrap_or(0);
+
+    // The font is monospace, padding is enough to get columns
+    let lines: Vec<String> = KEY_BINDINGS
+        .iter()
+        .map(|b| {
+            format!(
+                "{:keys_width$}  {:description_width$}  {}",
+                b.keys, b.description, b.plugin
+            )
+        })
+        .collect();
+
+    commands
+        .spawn((
+            HelpOverlay,
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    left: Val::Px(20.0),
+                    top: Val::Px(20.0),
+                    max_height: Val::Percent(90.0),
+                    padding: UiRect::all(Val::Px(10.0)),
+                    overflow: Overflow::clip(),
+                    ..default()
+                },
+                background_color: BackgroundColor(Color::srgba(0.1, 0.1, 0.1, 0.9)),
+                visibility: Visibility::Hidden,
+                // Above the toolbar and the context menu
+                z_index: ZIndex::Global(2),
+                ..default()
+            },
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(lines.join("\n"), text_style));
+        });
+}
+
+fn handle_toggle_help_event(
+    mut events: EventReader<ToggleHelpEvent>,
+    mut overlay_q: Query<&mut Visibility, With<HelpOverlay>>,
+) {
+    for _ in events.read() {
+        for mut visibility in &mut overlay_q {
+            *visibility = match *visibility {
+                Visibility::Hidden => Visibility::Visible,
+                _ => Visibility::Hidden,
+            };
+        }
+    }
+}
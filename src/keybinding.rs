@@ -1,9 +1,19 @@
 #![allow(clippy::needless_pass_by_value)]
 
 use crate::{
-    drawing::ClearEvent,
-    local_chalk::{ChangeColorEvent, GrowEvent, ShrinkEvent},
+    aging::ToggleStrokeAgingEvent,
+    display::ToggleInvertColorsEvent,
+    drawing::{ClearEvent, FlipAxis, FlipCanvasEvent, TranslateCanvasEvent},
+    help::ToggleHelpEvent,
+    local_chalk::{
+        ChangeColorEvent, GrowEvent, SelectColorEvent, ShrinkEvent, ToggleSizeStepModeEvent,
+        UndoEvent,
+    },
+    persistence::{LoadSceneEvent, SaveSceneEvent},
+    recording::{ChangePlaybackSpeedEvent, StartPlaybackEvent, ToggleRecordingEvent},
+    selection::{DeselectStrokeEvent, SelectNextStrokeEvent, SelectPreviousStrokeEvent},
     ui::ToggleUiEvent,
+    viewport::ZoomToFitEvent,
 };
 use bevy::prelude::*;
 
@@ -11,10 +21,111 @@ pub(crate) struct KeybindingPlugin;
 
 impl Plugin for KeybindingPlugin {
     fn build(&self, app: &mut bevy::prelude::App) {
-        app.add_systems(Update, update);
+        app.add_systems(Update, update)
+            .add_systems(Update, ctrl_shortcuts)
+            .add_systems(Update, selection_shortcuts)
+            .add_systems(Update, recording_shortcuts)
+            .add_systems(Update, display_shortcuts)
+            .add_systems(Update, canvas_shortcuts)
+            .add_systems(Update, help_shortcuts);
+
+        #[cfg(target_os = "android")]
+        app.add_systems(Update, android_back_button);
+    }
+}
+
+/// A shortcut, as shown by the help overlay
+pub(crate) struct KeyBinding {
+    pub(crate) keys: &'static str,
+    pub(crate) description: &'static str,
+    /// The plugin handling the action
+    pub(crate) plugin: &'static str,
+}
+
+const fn binding(
+    keys: &'static str,
+    description: &'static str,
+    plugin: &'static str,
+) -> KeyBinding {
+    KeyBinding {
+        keys,
+        description,
+        plugin,
     }
 }
 
+/// Every shortcut handled here, keep it in sync with the systems below
+pub(crate) const KEY_BINDINGS: &[KeyBinding] = &[
+    binding("C", "Change the chalk color", "LocalChalkPlugin"),
+    binding("1-7", "Pick a palette color", "LocalChalkPlugin"),
+    binding("M", "Grow chalk size", "LocalChalkPlugin"),
+    binding("N", "Shrink chalk size", "LocalChalkPlugin"),
+    binding("X", "Clear the whole blackboard", "DrawingPlugin"),
+    binding("U", "Toggle toolbar visibility", "UiPlugin"),
+    binding("Ctrl+Z", "Undo the last stroke", "LocalChalkPlugin"),
+    binding("Ctrl+S", "Save the drawing", "ScenePersistencePlugin"),
+    binding("Ctrl+O", "Load the drawing", "ScenePersistencePlugin"),
+    binding("Ctrl+A", "Fade old strokes over time", "StrokeAgingPlugin"),
+    binding("Ctrl+I", "Invert colors", "DisplayPlugin"),
+    binding("Ctrl+Shift+B", "Toggle size step mode", "LocalChalkPlugin"),
+    binding("Ctrl+0", "Show the whole drawing", "ViewportPlugin"),
+    binding(
+        "Ctrl+Shift+H",
+        "Mirror the drawing left-right",
+        "DrawingPlugin",
+    ),
+    binding(
+        "Ctrl+Shift+V",
+        "Mirror the drawing top-bottom",
+        "DrawingPlugin",
+    ),
+    binding("Ctrl+Shift+Arrows", "Move the drawing", "DrawingPlugin"),
+    binding("Ctrl+Shift+R", "Start or stop recording", "RecordingPlugin"),
+    binding(
+        "Ctrl+Shift+P",
+        "Replay the last recording",
+        "RecordingPlugin",
+    ),
+    binding(
+        "Ctrl+Shift+.",
+        "Double the playback speed",
+        "RecordingPlugin",
+    ),
+    binding(
+        "Ctrl+Shift+,",
+        "Halve the playback speed",
+        "RecordingPlugin",
+    ),
+    binding("Tab", "Select the next stroke", "KeyboardSelectionPlugin"),
+    binding(
+        "Shift+Tab",
+        "Select the previous stroke",
+        "KeyboardSelectionPlugin",
+    ),
+    binding(
+        "Esc",
+        "Clear the stroke selection",
+        "KeyboardSelectionPlugin",
+    ),
+    binding("F1 or ?", "Show or hide this help", "HelpOverlayPlugin"),
+];
+
+const COLOR_KEYS: [KeyCode; 9] = [
+    KeyCode::Digit1,
+    KeyCode::Digit2,
+    KeyCode::Digit3,
+    KeyCode::Digit4,
+    KeyCode::Digit5,
+    KeyCode::Digit6,
+    KeyCode::Digit7,
+    KeyCode::Digit8,
+    KeyCode::Digit9,
+];
+
+fn ctrl_pressed(keyboard_input: &ButtonInput<KeyCode>) -> bool {
+    keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight])
+}
+
 fn update(
     keyboard_input: Res<ButtonInput<KeyCode>>,
     mut clear_event: EventWriter<ClearEvent>,
@@ -22,6 +133,7 @@ fn update(
     mut shrink_event: EventWriter<ShrinkEvent>,
     mut grow_event: EventWriter<GrowEvent>,
     mut toggle_ui_event: EventWriter<ToggleUiEvent>,
+    mut select_color_event: EventWriter<SelectColorEvent>,
 ) {
     if keyboard_input.just_pressed(KeyCode::KeyX) {
         clear_event.send(ClearEvent::new());
@@ -42,4 +154,316 @@ fn update(
     if keyboard_input.just_pressed(KeyCode::KeyU) {
         toggle_ui_event.send(ToggleUiEvent);
     }
+
+    // 1 is the first palette color, keys past the palette end are ignored
+    for (index, key) in COLOR_KEYS.iter().enumerate() {
+        if keyboard_input.just_pressed(*key) {
+            select_color_event.send(SelectColorEvent(index));
+        }
+    }
+}
+
+fn ctrl_shortcuts(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut undo_event: EventWriter<UndoEvent>,
+    mut save_scene_event: EventWriter<SaveSceneEvent>,
+    mut load_scene_event: EventWriter<LoadSceneEvent>,
+    mut toggle_aging_event: EventWriter<ToggleStrokeAgingEvent>,
+    mut toggle_size_step_event: EventWriter<ToggleSizeStepModeEvent>,
+    mut zoom_to_fit_event: EventWriter<ZoomToFitEvent>,
+) {
+    if !ctrl_pressed(&keyboard_input) {
+        return;
+    }
+
+    let shift = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+
+    if keyboard_input.just_pressed(KeyCode::KeyZ) {
+        undo_event.send(UndoEvent);
+    }
+
+    if keyboard_input.just_pressed(KeyCode::KeyS) {
+        save_scene_event.send(SaveSceneEvent);
+    }
+
+    if keyboard_input.just_pressed(KeyCode::KeyO) {
+        load_scene_event.send(LoadSceneEvent);
+    }
+
+    if keyboard_input.just_pressed(KeyCode::KeyA) {
+        toggle_aging_event.send(ToggleStrokeAgingEvent);
+    }
+
+    if shift && keyboard_input.just_pressed(KeyCode::KeyB) {
+        toggle_size_step_event.send(ToggleSizeStepModeEvent);
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Digit0) {
+        zoom_to_fit_event.send(ZoomToFitEvent);
+    }
+}
+
+fn display_shortcuts(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut toggle_invert_colors_event: EventWriter<ToggleInvertColorsEvent>,
+) {
+    if ctrl_pressed(&keyboard_input) && keyboard_input.just_pressed(KeyCode::KeyI) {
+        toggle_invert_colors_event.send(ToggleInvertColorsEvent);
+    }
+}
+
+/// Distance the drawing is moved by with Ctrl+Shift+Arrow, in world units
+const TRANSLATE_STEP: f32 = 100.0;
+
+fn canvas_shortcuts(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut flip_canvas_event: EventWriter<FlipCanvasEvent>,
+    mut translate_canvas_event: EventWriter<TranslateCanvasEvent>,
+) {
+    let shift = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+
+    if !ctrl_pressed(&keyboard_input) || !shift {
+        return;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::KeyH) {
+        flip_canvas_event.send(FlipCanvasEvent {
+            axis: FlipAxis::Horizontal,
+        });
+    }
+
+    if keyboard_input.just_pressed(KeyCode::KeyV) {
+        flip_canvas_event.send(FlipCanvasEvent {
+            axis: FlipAxis::Vertical,
+        });
+    }
+
+    for (key, direction) in [
+        (KeyCode::ArrowLeft, Vec2::NEG_X),
+        (KeyCode::ArrowRight, Vec2::X),
+        (KeyCode::ArrowUp, Vec2::Y),
+        (KeyCode::ArrowDown, Vec2::NEG_Y),
+    ] {
+        if keyboard_input.just_pressed(key) {
+            translate_canvas_event.send(TranslateCanvasEvent {
+                delta: direction * TRANSLATE_STEP,
+            });
+        }
+    }
+}
+
+fn recording_shortcuts(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut toggle_recording_event: EventWriter<ToggleRecordingEvent>,
+    mut start_playback_event: EventWriter<StartPlaybackEvent>,
+    mut change_speed_event: EventWriter<ChangePlaybackSpeedEvent>,
+) {
+    let shift = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+
+    if !ctrl_pressed(&keyboard_input) || !shift {
+        return;
+    }
+
+    if keyboard_input.just_pressed(KeyCode::KeyR) {
+        toggle_recording_event.send(ToggleRecordingEvent);
+    }
+
+    if keyboard_input.just_pressed(KeyCode::KeyP) {
+        start_playback_event.send(StartPlaybackEvent);
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Period) {
+        change_speed_event.send(ChangePlaybackSpeedEvent(2.0));
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Comma) {
+        change_speed_event.send(ChangePlaybackSpeedEvent(0.5));
+    }
+}
+
+fn help_shortcuts(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut toggle_help_event: EventWriter<ToggleHelpEvent>,
+) {
+    let shift = keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]);
+
+    // '?' is Shift+/ on US layouts
+    if keyboard_input.just_pressed(KeyCode::F1)
+        || (shift && keyboard_input.just_pressed(KeyCode::Slash))
+    {
+        toggle_help_event.send(ToggleHelpEvent);
+    }
+}
+
+fn selection_shortcuts(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut next_event: EventWriter<SelectNextStrokeEvent>,
+    mut previous_event: EventWriter<SelectPreviousStrokeEvent>,
+    mut deselect_event: EventWriter<DeselectStrokeEvent>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Tab) {
+        if keyboard_input.any_pressed([KeyCode::ShiftLeft, KeyCode::ShiftRight]) {
+            previous_event.send(SelectPreviousStrokeEvent);
+        } else {
+            next_event.send(SelectNextStrokeEvent);
+        }
+    }
+
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        deselect_event.send(DeselectStrokeEvent);
+    }
+}
+
+/// On Android the hardware back button undoes the last stroke, instead of
+/// leaving the app. On desktop the window is closed with its close button.
+#[cfg(target_os = "android")]
+fn android_back_button(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut undo_event: EventWriter<UndoEvent>,
+) {
+    if keyboard_input.just_pressed(KeyCode::BrowserBack) {
+        undo_event.send(UndoEvent);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Run the keybindings for one frame, with `keys` just pressed
+    fn press(keys: &[KeyCode]) -> App {
+        let mut app = App::new();
+
+        app.add_plugins(KeybindingPlugin)
+            .add_event::<ClearEvent>()
+            .add_event::<ChangeColorEvent>()
+            .add_event::<ShrinkEvent>()
+            .add_event::<GrowEvent>()
+            .add_event::<ToggleUiEvent>()
+            .add_event::<SelectColorEvent>()
+            .add_event::<UndoEvent>()
+            .add_event::<SaveSceneEvent>()
+            .add_event::<LoadSceneEvent>()
+            .add_event::<ToggleStrokeAgingEvent>()
+            .add_event::<ToggleSizeStepModeEvent>()
+            .add_event::<ZoomToFitEvent>()
+            .add_event::<ToggleInvertColorsEvent>()
+            .add_event::<FlipCanvasEvent>()
+            .add_event::<TranslateCanvasEvent>()
+            .add_event::<ToggleRecordingEvent>()
+            .add_event::<StartPlaybackEvent>()
+            .add_event::<ChangePlaybackSpeedEvent>()
+            .add_event::<ToggleHelpEvent>()
+            .add_event::<SelectNextStrokeEvent>()
+            .add_event::<SelectPreviousStrokeEvent>()
+            .add_event::<DeselectStrokeEvent>();
+
+        let mut input = ButtonInput::<KeyCode>::default();
+        for key in keys {
+            input.press(*key);
+        }
+        app.insert_resource(input);
+
+        app.update();
+        app
+    }
+
+    fn sent<E: Event>(app: &App) -> usize {
+        app.world().resource::<Events<E>>().len()
+    }
+
+    #[test]
+    fn number_keys_select_palette_colors() {
+        let app = press(&[KeyCode::Digit3]);
+
+        let events = app.world().resource::<Events<SelectColorEvent>>();
+        let indices: Vec<usize> = events.get_reader().read(events).map(|e| e.0).collect();
+
+        assert_eq!(indices, vec![2]);
+    }
+
+    #[test]
+    fn number_keys_past_the_keys_array_send_nothing() {
+        let app = press(&[KeyCode::Digit0]);
+
+        assert_eq!(sent::<SelectColorEvent>(&app), 0);
+    }
+
+    fn sends<E: Event>(keys: &[KeyCode]) -> bool {
+        sent::<E>(&press(keys)) == 1
+    }
+
+    const CTRL: KeyCode = KeyCode::ControlLeft;
+    const SHIFT: KeyCode = KeyCode::ShiftLeft;
+
+    #[test]
+    fn single_keys_send_their_events() {
+        assert!(sends::<ClearEvent>(&[KeyCode::KeyX]));
+        assert!(sends::<ChangeColorEvent>(&[KeyCode::KeyC]));
+        assert!(sends::<GrowEvent>(&[KeyCode::KeyM]));
+        assert!(sends::<ShrinkEvent>(&[KeyCode::KeyN]));
+        assert!(sends::<ToggleUiEvent>(&[KeyCode::KeyU]));
+    }
+
+    #[test]
+    fn ctrl_shortcuts_send_their_events() {
+        assert!(sends::<UndoEvent>(&[CTRL, KeyCode::KeyZ]));
+        assert!(sends::<SaveSceneEvent>(&[CTRL, KeyCode::KeyS]));
+        assert!(sends::<LoadSceneEvent>(&[CTRL, KeyCode::KeyO]));
+        assert!(sends::<ToggleStrokeAgingEvent>(&[CTRL, KeyCode::KeyA]));
+        assert!(sends::<ToggleInvertColorsEvent>(&[CTRL, KeyCode::KeyI]));
+        assert!(sends::<ZoomToFitEvent>(&[CTRL, KeyCode::Digit0]));
+        assert!(sends::<ToggleSizeStepModeEvent>(&[
+            CTRL,
+            SHIFT,
+            KeyCode::KeyB
+        ]));
+    }
+
+    #[test]
+    fn ctrl_shortcuts_need_ctrl() {
+        assert!(!sends::<UndoEvent>(&[KeyCode::KeyZ]));
+        assert!(!sends::<SaveSceneEvent>(&[KeyCode::KeyS]));
+        assert!(!sends::<ToggleSizeStepModeEvent>(&[SHIFT, KeyCode::KeyB]));
+    }
+
+    #[test]
+    fn canvas_shortcuts_send_their_events() {
+        assert!(sends::<FlipCanvasEvent>(&[CTRL, SHIFT, KeyCode::KeyH]));
+        assert!(sends::<FlipCanvasEvent>(&[CTRL, SHIFT, KeyCode::KeyV]));
+        assert!(!sends::<FlipCanvasEvent>(&[CTRL, KeyCode::KeyH]));
+
+        let app = press(&[CTRL, SHIFT, KeyCode::ArrowLeft]);
+        let events = app.world().resource::<Events<TranslateCanvasEvent>>();
+        let deltas: Vec<Vec2> = events.get_reader().read(events).map(|e| e.delta).collect();
+
+        assert_eq!(deltas, vec![Vec2::new(-TRANSLATE_STEP, 0.0)]);
+    }
+
+    #[test]
+    fn recording_shortcuts_send_their_events() {
+        assert!(sends::<ToggleRecordingEvent>(&[CTRL, SHIFT, KeyCode::KeyR]));
+        assert!(sends::<StartPlaybackEvent>(&[CTRL, SHIFT, KeyCode::KeyP]));
+        assert!(sends::<ChangePlaybackSpeedEvent>(&[
+            CTRL,
+            SHIFT,
+            KeyCode::Period
+        ]));
+        assert!(sends::<ChangePlaybackSpeedEvent>(&[
+            CTRL,
+            SHIFT,
+            KeyCode::Comma
+        ]));
+    }
+
+    #[test]
+    fn help_and_selection_shortcuts_send_their_events() {
+        assert!(sends::<ToggleHelpEvent>(&[KeyCode::F1]));
+        assert!(sends::<ToggleHelpEvent>(&[SHIFT, KeyCode::Slash]));
+        assert!(!sends::<ToggleHelpEvent>(&[KeyCode::Slash]));
+        assert!(sends::<SelectNextStrokeEvent>(&[KeyCode::Tab]));
+        assert!(sends::<SelectPreviousStrokeEvent>(&[SHIFT, KeyCode::Tab]));
+        assert!(!sends::<SelectNextStrokeEvent>(&[SHIFT, KeyCode::Tab]));
+        assert!(sends::<DeselectStrokeEvent>(&[KeyCode::Escape]));
+    }
 }
@@ -1,16 +1,36 @@
 #![deny(unsafe_code)]
 #![warn(clippy::all, clippy::pedantic)]
 
+mod aging;
 mod collab;
+mod color_math;
+mod context_menu;
 mod debug;
+mod diagnostics;
+mod display;
 mod drawing;
+mod gamepad;
+mod help;
 mod keybinding;
 mod local_chalk;
+mod palm_rejection;
+mod persistence;
+mod recording;
+mod region_erase;
+mod selection;
+#[cfg(feature = "lavagna_serde")]
+mod serde_color;
+mod signal_processing;
 mod ui;
+mod viewport;
+mod window_icon;
 
 #[cfg(not(target_arch = "wasm32"))]
 pub mod cli;
 
+#[cfg(not(target_arch = "wasm32"))]
+mod network_config;
+
 #[cfg(target_arch = "wasm32")]
 pub mod web;
 
@@ -20,14 +40,35 @@ use bevy::prelude::*;
 use bevy_embedded_assets::EmbeddedAssetPlugin;
 use bevy_framepace::{FramepacePlugin, FramepaceSettings, Limiter};
 use bevy_pancam::{PanCam, PanCamPlugin};
+#[cfg(feature = "lavagna_serde")]
+use serde::{Deserialize, Serialize};
 
+use crate::aging::StrokeAgingPlugin;
 use crate::collab::CollabPlugin;
 pub use crate::collab::CollabPluginOpt as CollabOpt;
-use crate::debug::DebugPlugin;
-use crate::drawing::DrawingPlugin;
+pub use crate::collab::IceServer;
+use crate::context_menu::ContextMenuPlugin;
+use crate::debug::{DebugPlugin, DiagnosticsOverlayPlugin};
+use crate::diagnostics::DrawingDiagnosticsPlugin;
+use crate::display::DisplayPlugin;
+pub use crate::drawing::svg_transform;
+pub use crate::drawing::DrawingPlugin;
+pub use crate::drawing::NoiseFilterConfig;
+pub use crate::drawing::Polyline;
+pub use crate::drawing::TessellationQuality;
+use crate::gamepad::GamepadDrawingPlugin;
+use crate::help::HelpOverlayPlugin;
 use crate::keybinding::KeybindingPlugin;
-use crate::local_chalk::LocalChalkPlugin;
+pub use crate::local_chalk::DrawEvent;
+use crate::local_chalk::{LocalChalk, LocalChalkPlugin};
+use crate::palm_rejection::PalmRejectionPlugin;
+use crate::persistence::ScenePersistencePlugin;
+use crate::recording::RecordingPlugin;
+use crate::region_erase::RegionErasePlugin;
+use crate::selection::KeyboardSelectionPlugin;
 use crate::ui::UiPlugin;
+use crate::viewport::ViewportPlugin;
+use crate::window_icon::WindowIconPlugin;
 
 #[derive(Debug, Default)]
 pub struct Opt {
@@ -35,18 +76,54 @@ pub struct Opt {
     pub show_debug_pane: bool,
     pub verbose: bool,
     pub ui: bool,
+    pub spectate: bool,
+    /// Initial chalk color, white if not set
+    pub chalk_color: Option<Srgba>,
+    /// Initial chalk line width, in pixels
+    pub chalk_line_width: Option<u32>,
+    /// Blackboard color, black if not set
+    pub background_color: Option<Color>,
+    /// Window width, in logical pixels
+    pub width: Option<u32>,
+    /// Window height, in logical pixels
+    pub height: Option<u32>,
+    /// The network configuration file could not be used, reported once
+    /// logging is initialized
+    pub network_config_error: Option<String>,
 }
 
 pub fn run(opt: Opt) {
     let mut app = App::new();
 
+    let mode = if opt.spectate {
+        AppMode::Spectating
+    } else {
+        AppMode::Drawing
+    };
+
+    let title = match mode {
+        AppMode::Drawing => "lavagna",
+        AppMode::Spectating => "lavagna (Spectating)",
+    };
+
+    let mut window = Window {
+        title: title.to_owned(),
+        // Avoid scrolling on mobile
+        prevent_default_event_handling: true,
+        canvas: Some("#bevy".to_owned()),
+        ..default()
+    };
+
+    // The default size is kept for the missing dimension
+    #[allow(clippy::cast_precision_loss)]
+    let (width, height) = (
+        opt.width.map_or(window.resolution.width(), |w| w as f32),
+        opt.height.map_or(window.resolution.height(), |h| h as f32),
+    );
+    window.resolution.set(width, height);
+
     let window_plugin = WindowPlugin {
-        primary_window: Some(Window {
-            // Avoid scrolling on mobile
-            prevent_default_event_handling: true,
-            canvas: Some("#bevy".to_owned()),
-            ..default()
-        }),
+        primary_window: Some(window),
         ..default()
     };
 
@@ -71,21 +148,53 @@ pub fn run(opt: Opt) {
             .add_before::<bevy::asset::AssetPlugin, _>(EmbeddedAssetPlugin::default()),
     );
 
+    if let Some(err) = &opt.network_config_error {
+        log::error!("{err}");
+    }
+
     app.insert_resource(Stats::default());
+    app.insert_resource(mode);
 
     app.add_systems(Startup, setup);
 
+    app.register_type::<Chalk>();
+
     app.add_plugins(KeybindingPlugin);
+    app.add_plugins(HelpOverlayPlugin);
     app.add_plugins(FramepacePlugin);
-    app.add_plugins(LocalChalkPlugin);
-    app.add_plugins(DrawingPlugin);
+    app.add_plugins(PalmRejectionPlugin);
+    app.add_plugins(LocalChalkPlugin::default().with_default_chalk(startup_chalk(&opt)));
+
+    let mut drawing_plugin = DrawingPlugin::default();
+    if let Some(color) = opt.background_color {
+        drawing_plugin = drawing_plugin.with_background_color(color);
+    }
+    app.add_plugins(drawing_plugin);
+    app.add_plugins(DrawingDiagnosticsPlugin);
+    app.add_plugins(StrokeAgingPlugin);
+    app.add_plugins(ScenePersistencePlugin);
+    app.add_plugins(RecordingPlugin);
+    app.add_plugins(KeyboardSelectionPlugin);
+    app.add_plugins(RegionErasePlugin);
+    app.add_plugins(ContextMenuPlugin);
+    app.add_plugins(GamepadDrawingPlugin);
     app.add_plugins(PanCamPlugin);
+    app.add_plugins(ViewportPlugin);
+    app.add_plugins(WindowIconPlugin);
+    app.add_plugins(DisplayPlugin);
 
     if opt.show_debug_pane {
         app.add_plugins(FrameTimeDiagnosticsPlugin);
         app.add_plugins(DebugPlugin);
+        app.add_plugins(DiagnosticsOverlayPlugin);
     }
 
+    #[cfg(feature = "debug")]
+    app.add_plugins(debug::InspectorPlugin);
+
+    #[cfg(feature = "debug")]
+    app.add_plugins(debug::EntityHudPlugin);
+
     if let Some(collab_opt) = opt.collab {
         app.add_plugins(CollabPlugin::new(collab_opt));
     }
@@ -95,14 +204,34 @@ pub fn run(opt: Opt) {
     app.run();
 }
 
+/// The local chalk at startup, with the color and line width from options
+#[allow(clippy::cast_precision_loss)]
+fn startup_chalk(opt: &Opt) -> Chalk {
+    let mut chalk = *LocalChalk::default().get();
+
+    if let Some(color) = opt.chalk_color {
+        chalk.color = color;
+    }
+
+    if let Some(line_width) = opt.chalk_line_width {
+        chalk.line_width = line_width as f32;
+    }
+
+    chalk
+}
+
+/// Spectators can see what peers draw, but they cannot draw
+#[derive(Resource, Debug, Clone, Copy, PartialEq, Eq, Default)]
+enum AppMode {
+    #[default]
+    Drawing,
+    Spectating,
+}
+
 #[derive(Component)]
 struct MainCamera;
 
-fn setup(
-    mut commands: Commands,
-    mut clear_color: ResMut<ClearColor>,
-    mut framepace: ResMut<FramepaceSettings>,
-) {
+fn setup(mut commands: Commands, mut framepace: ResMut<FramepaceSettings>) {
     commands
         .spawn((Camera2dBundle::default(), MainCamera))
         .insert(PanCam {
@@ -117,21 +246,43 @@ fn setup(
             max_y: None,
         });
 
-    clear_color.0 = Color::BLACK;
-
     // Limit frame rate, we dont't want to squeeze that CPU
     framepace.limiter = Limiter::from_framerate(30.0);
 }
 
-#[derive(Component, Debug, Clone, Copy, Default)]
+#[derive(Component, Debug, Clone, Copy, PartialEq, Default, Reflect)]
+#[reflect(Component)]
+#[cfg_attr(feature = "lavagna_serde", derive(Serialize, Deserialize))]
 struct Chalk {
     pressed: bool,
     updated: bool,
     just_released: bool,
-    x: i32,
-    y: i32,
+    x: f32,
+    y: f32,
+    #[cfg_attr(feature = "lavagna_serde", serde(with = "serde_color"))]
     color: Srgba,
-    line_width: u32,
+    line_width: f32,
+}
+
+impl Chalk {
+    /// Whether the chalk moved, was pressed or was released since `prev`.
+    /// A new color or line width alone adds nothing to the stroke.
+    ///
+    /// Not a plain `!=`: the `updated` flag itself would take part in the
+    /// comparison, and a width change would add a point. `Eq` can't be
+    /// derived because of the `f32` fields; the derived `PartialEq` is still
+    /// what `set_if_neq` uses to skip writing an unchanged chalk.
+    fn is_updated_from(&self, prev: &Chalk) -> bool {
+        Vec2::new(self.x, self.y) != Vec2::new(prev.x, prev.y)
+            || self.pressed != prev.pressed
+            || self.just_released != prev.just_released
+    }
+
+    /// Line width, rounded to whole pixels
+    #[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+    fn rounded_line_width(&self) -> u32 {
+        self.line_width.round() as u32
+    }
 }
 
 #[must_use]
@@ -157,3 +308,57 @@ pub struct CollabStats {
 pub struct Stats {
     pub collab: CollabStats,
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn identical_chalk_is_not_updated() {
+        let chalk = Chalk {
+            x: 1.0,
+            y: 2.0,
+            pressed: true,
+            ..default()
+        };
+
+        assert!(!chalk.is_updated_from(&chalk));
+    }
+
+    #[test]
+    fn moved_chalk_is_updated() {
+        let prev = Chalk::default();
+        let chalk = Chalk { x: 0.5, ..prev };
+
+        assert!(chalk.is_updated_from(&prev));
+    }
+
+    #[test]
+    fn pressed_or_released_chalk_is_updated() {
+        let prev = Chalk::default();
+
+        let pressed = Chalk {
+            pressed: true,
+            ..prev
+        };
+        assert!(pressed.is_updated_from(&prev));
+
+        let released = Chalk {
+            just_released: true,
+            ..prev
+        };
+        assert!(released.is_updated_from(&prev));
+    }
+
+    #[test]
+    fn new_color_or_width_alone_is_not_an_update() {
+        let prev = Chalk::default();
+        let chalk = Chalk {
+            color: Srgba::rgb(1.0, 0.0, 0.0),
+            line_width: 12.0,
+            ..prev
+        };
+
+        assert!(!chalk.is_updated_from(&prev));
+    }
+}
@@ -1,29 +1,168 @@
 #![allow(clippy::needless_pass_by_value)]
 
-use crate::drawing::make_chalk;
+use crate::collab::in_room;
+use crate::color_math::contrast_color;
+use crate::drawing::{
+    self, make_chalk, CanvasBounds, EraserGhost, History, PredictedPoint, Undone,
+};
+use crate::palm_rejection::FilteredTouchInput;
+use crate::AppMode;
 use crate::Chalk;
 use crate::MainCamera;
 use bevy::color::palettes::css::{BLUE, GREEN, ORANGE, RED, TURQUOISE, WHITE, YELLOW};
-use std::cmp::max;
-use std::cmp::min;
+use bevy::utils::{Duration, HashMap};
+use bevy_pancam::PanCamSystemSet;
+use std::collections::VecDeque;
 
 use bevy::{
-    input::{mouse::MouseButtonInput, ButtonState},
+    ecs::event::ManualEventReader,
+    input::{
+        mouse::{MouseButtonInput, MouseWheel},
+        ButtonState,
+    },
     prelude::*,
+    window::{CursorLeft, CursorMoved},
 };
 use bevy_prototype_lyon::prelude::Fill;
 use bevy_prototype_lyon::prelude::GeometryBuilder;
 use bevy_prototype_lyon::prelude::ShapeBundle;
+use bevy_prototype_lyon::prelude::Stroke;
 use bevy_prototype_lyon::shapes;
 
-pub(crate) struct LocalChalkPlugin;
+pub(crate) struct LocalChalkPlugin {
+    default_chalk: Chalk,
+}
+
+impl Default for LocalChalkPlugin {
+    fn default() -> Self {
+        Self {
+            default_chalk: LocalChalk::default().0,
+        }
+    }
+}
+
+impl LocalChalkPlugin {
+    /// The chalk at startup, for its color and line width
+    pub(crate) fn with_default_chalk(mut self, chalk: Chalk) -> Self {
+        self.default_chalk = chalk;
+        self
+    }
+}
 
 #[derive(Component)]
-struct LocalChalkFlag;
+pub(crate) struct LocalChalkFlag;
 
+/// A ring showing the chalk color and the actual line width
 #[derive(Component)]
 struct LocalCursor;
 
+/// A dot showing the exact drawing point, inside the ring
+#[derive(Component)]
+struct LocalCursorDot;
+
+/// Ring thickness, in pixels
+const CURSOR_RING_WIDTH: f32 = 1.0;
+
+/// When the line width changes, the cursor grows and shrinks back, so that
+/// the change is clearly visible
+#[derive(Component)]
+struct BrushSizePulse {
+    timer: Timer,
+    phase: PulsePhase,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum PulsePhase {
+    Growing,
+    Shrinking,
+}
+
+/// Duration of each pulse phase
+const PULSE_PHASE_DURATION: Duration = Duration::from_millis(100);
+
+/// Maximum cursor scale during the pulse
+const PULSE_SCALE: f32 = 1.5;
+
+impl BrushSizePulse {
+    fn new() -> Self {
+        Self {
+            timer: Timer::new(PULSE_PHASE_DURATION, TimerMode::Once),
+            phase: PulsePhase::Growing,
+        }
+    }
+
+    fn scale(&self, easing: EasingConfig) -> f32 {
+        let t = easing.apply(self.timer.fraction());
+
+        let t = match self.phase {
+            PulsePhase::Growing => t,
+            PulsePhase::Shrinking => 1.0 - t,
+        };
+
+        1.0 + (PULSE_SCALE - 1.0) * t
+    }
+}
+
+/// Easing curve of cursor animations
+#[derive(Resource, Debug, Clone, Copy, Default)]
+pub(crate) enum EasingConfig {
+    Linear,
+    #[default]
+    EaseInOut,
+}
+
+impl EasingConfig {
+    fn apply(self, t: f32) -> f32 {
+        match self {
+            Self::Linear => t,
+            Self::EaseInOut => t * t * (3.0 - 2.0 * t),
+        }
+    }
+}
+
+/// What last moved the local chalk. The mouse takes the chalk back only when
+/// the cursor moves, a still cursor must not pull the chalk away from the
+/// other sources.
+#[derive(Resource, Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) enum ChalkSource {
+    #[default]
+    Mouse,
+    /// [`DrawEvent`]s
+    Script,
+    /// The left stick, see `gamepad_cursor`
+    Gamepad,
+    /// The first touch on the screen, see `touch_events`
+    Touch,
+}
+
+/// [`DrawEvent`]s not applied yet, see [`handle_draw_event`]
+#[derive(Resource, Debug, Default)]
+struct DrawQueue(VecDeque<DrawEvent>);
+
+/// The local stroke is extrapolated `predict_ms` ahead of the cursor, so
+/// that it seems to follow the cursor without lag. Zero disables it.
+#[derive(Resource, Debug, Clone, Copy)]
+pub(crate) struct LatencyCompensation {
+    predict_ms: f32,
+}
+
+impl Default for LatencyCompensation {
+    fn default() -> Self {
+        Self { predict_ms: 20.0 }
+    }
+}
+
+/// When a prediction misses the actual position by more than this, in
+/// pixels, the next one is not shown (e.g. after a sharp turn)
+const MAX_PREDICTION_ERROR: f32 = 20.0;
+
+#[derive(Default)]
+struct CursorPrediction {
+    /// Last known position and when it was reached, in seconds
+    last: Option<(Vec2, f32)>,
+    predicted: Option<Vec2>,
+}
+
 #[derive(Resource)]
 pub(crate) struct LocalChalk(Chalk);
 
@@ -37,7 +176,7 @@ impl Default for LocalChalk {
     fn default() -> Self {
         Self(Chalk {
             color: WHITE,
-            line_width: 8,
+            line_width: 8.0,
             ..default()
         })
     }
@@ -45,19 +184,62 @@ impl Default for LocalChalk {
 
 impl Plugin for LocalChalkPlugin {
     fn build(&self, app: &mut App) {
-        app.init_resource::<LocalChalk>()
+        app.insert_resource(LocalChalk(self.default_chalk))
+            .init_resource::<EasingConfig>()
+            .init_resource::<SizeStepConfig>()
+            .init_resource::<LatencyCompensation>()
+            .init_resource::<ActiveTouches>()
+            .init_resource::<ChalkSource>()
+            .init_resource::<DrawQueue>()
             .add_event::<ChangeColorEvent>()
+            .add_event::<SelectColorEvent>()
             .add_event::<GrowEvent>()
             .add_event::<ShrinkEvent>()
+            .add_event::<UndoEvent>()
+            .add_event::<StrokeUndoneEvent>()
+            .add_event::<ToggleSizeStepModeEvent>()
+            .add_event::<DrawEvent>()
             .add_systems(Startup, startup)
             .add_systems(Update, handle_user_input)
             .add_systems(Update, handle_change_color_event)
+            .add_systems(Update, handle_select_color_event)
             .add_systems(Update, handle_incr_size_event)
             .add_systems(Update, handle_decr_size_event)
-            .add_systems(Update, mouse_events)
-            .add_systems(Update, update_chalk)
-            .add_systems(Update, touch_events)
+            .add_systems(Update, handle_undo_event)
+            .add_systems(Update, handle_toggle_size_step_mode_event)
+            .add_systems(
+                Update,
+                handle_draw_event
+                    .after(handle_user_input)
+                    .before(update_chalk),
+            )
+            .add_systems(Update, update_chalk.before(drawing::update))
+            .add_systems(Update, predict_chalk_position.after(handle_user_input))
+            .add_systems(Update, start_brush_size_pulse)
+            .add_systems(Update, tick_brush_size_pulse)
             .add_systems(Update, update_cursor);
+
+        let mode = app
+            .world()
+            .get_resource::<AppMode>()
+            .copied()
+            .unwrap_or_default();
+
+        // Spectators move the cursor, but never press the chalk
+        if mode == AppMode::Drawing {
+            app.add_systems(Update, mouse_events)
+                .add_systems(Update, mouse_wheel_events.before(PanCamSystemSet))
+                // A stale mouse cursor must not move the chalk away from
+                // the touch, touch wins
+                .add_systems(Update, touch_events.after(handle_user_input))
+                .add_systems(
+                    Update,
+                    update_touch_chalks
+                        .after(touch_events)
+                        .before(drawing::update)
+                        .run_if(not(in_room)),
+                );
+        }
     }
 }
 
@@ -65,6 +247,7 @@ fn startup(mut commands: Commands, mut chalk: ResMut<LocalChalk>) {
     commands.spawn((
         LocalChalkFlag, //
         make_chalk(chalk.0),
+        PredictedPoint::default(),
     ));
 
     let chalk = &mut chalk.0;
@@ -80,7 +263,16 @@ fn startup(mut commands: Commands, mut chalk: ResMut<LocalChalk>) {
             path: GeometryBuilder::build_as(&shape),
             ..default()
         },
-        Fill::color(chalk.color),
+        Stroke::new(chalk.color, CURSOR_RING_WIDTH),
+    ));
+
+    commands.spawn((
+        LocalCursorDot,
+        ShapeBundle {
+            path: GeometryBuilder::build_as(&shape),
+            ..default()
+        },
+        Fill::color(contrast_color(chalk.color)),
     ));
 }
 
@@ -94,12 +286,33 @@ fn cursor_to_world_position(
     Some(Vec2::new(world_position[0], world_position[1]))
 }
 
-#[allow(clippy::cast_possible_truncation)]
-fn handle_user_input(
+#[allow(clippy::too_many_arguments)]
+pub(crate) fn handle_user_input(
     window_q: Query<&Window>,
     camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
     mut chalk: ResMut<LocalChalk>,
+    bounds: Res<CanvasBounds>,
+    mut cursor_moved_events: EventReader<CursorMoved>,
+    mut source: ResMut<ChalkSource>,
+    draw_queue: Res<DrawQueue>,
+    held_touches: Res<Touches>,
 ) {
+    // Pending scripted events and held touches keep the chalk, even if the
+    // mouse moves (browsers move the mouse cursor along with touches)
+    let mouse_may_take_over = draw_queue.0.is_empty() && held_touches.iter().next().is_none();
+
+    if mouse_may_take_over && cursor_moved_events.read().count() > 0 {
+        *source = ChalkSource::Mouse;
+    }
+
+    cursor_moved_events.clear();
+
+    if *source != ChalkSource::Mouse {
+        // The chalk did not move since the drawing last saw it
+        chalk.0.updated = false;
+        return;
+    }
+
     let (camera, camera_transform) = camera_q.single();
     let chalk = &mut chalk.0;
     let window = window_q.single();
@@ -111,14 +324,64 @@ fn handle_user_input(
     };
 
     if let Some(world_position) = cursor_to_world_position(cursor_pos, camera, camera_transform) {
-        chalk.x = world_position[0] as i32;
-        chalk.y = world_position[1] as i32;
-        chalk.updated = is_updated(&prev_chalk, chalk);
+        let world_position = bounds.clamp(world_position);
+        chalk.x = world_position[0];
+        chalk.y = world_position[1];
+        chalk.updated = chalk.is_updated_from(&prev_chalk);
+    }
+}
+
+fn predict_chalk_position(
+    chalk: Res<LocalChalk>,
+    config: Res<LatencyCompensation>,
+    time: Res<Time>,
+    mut predicted_q: Query<&mut PredictedPoint, With<LocalChalkFlag>>,
+    projection_q: Query<&OrthographicProjection, With<MainCamera>>,
+    mut state: Local<CursorPrediction>,
+) {
+    let chalk = &chalk.0;
+
+    // Positions are in world units, a zoomed out pixel covers more of them
+    let max_error = projection_q
+        .get_single()
+        .map_or(1.0, |projection| projection.scale)
+        * MAX_PREDICTION_ERROR;
+
+    if chalk.updated {
+        let now = time.elapsed_seconds();
+        let position = Vec2::new(chalk.x, chalk.y);
+
+        // A bad prediction is rolled back: the stroke ends at the actual
+        // position and the next prediction is skipped
+        let reliable = state
+            .predicted
+            .map_or(true, |predicted| predicted.distance(position) <= max_error);
+
+        let enabled = config.predict_ms > 0.0 && chalk.pressed && reliable;
+
+        state.predicted = match state.last {
+            Some((last, last_time)) if enabled && now > last_time => {
+                let velocity = (position - last) / (now - last_time);
+                Some(position + velocity * config.predict_ms / 1000.0)
+            }
+            _ => None,
+        };
+
+        state.last = Some((position, now));
+    } else {
+        // The cursor stopped, nothing to extrapolate
+        state.predicted = None;
+    }
+
+    if let Ok(mut predicted) = predicted_q.get_single_mut() {
+        predicted.0 = state.predicted;
     }
 }
 
 fn mouse_events(
     mut mouse_button_input_events: EventReader<MouseButtonInput>,
+    mut cursor_left_events: EventReader<CursorLeft>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
     mut chalk: ResMut<LocalChalk>,
 ) {
     let chalk = &mut chalk.0;
@@ -127,13 +390,16 @@ fn mouse_events(
     // This is needed to avoid interference with touch (see touch_events)
     let mut press_changed = false;
 
+    // Ctrl + drag selects a region to erase, see region_erase
+    let ctrl = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+
     for event in &mut mouse_button_input_events.read() {
         match event {
             MouseButtonInput {
                 button: MouseButton::Left,
                 state: ButtonState::Pressed,
                 ..
-            } => {
+            } if !ctrl => {
                 chalk.just_released = false;
                 chalk.pressed = true;
                 press_changed = true;
@@ -150,6 +416,14 @@ fn mouse_events(
         }
     }
 
+    // The button may be released outside of the window, without any event.
+    // Release the chalk now, otherwise a line would be drawn from where the
+    // cursor left to where it enters again.
+    if cursor_left_events.read().count() > 0 && chalk.pressed {
+        chalk.pressed = false;
+        press_changed = true;
+    }
+
     if !press_changed {
         return;
     }
@@ -157,11 +431,68 @@ fn mouse_events(
     chalk.just_released = was_pressed && !chalk.pressed;
 }
 
-#[allow(clippy::cast_possible_truncation)]
+/// Ctrl + mouse wheel changes the chalk size. The wheel events are consumed,
+/// so the camera doesn't zoom, but it can still be panned.
+pub(crate) fn mouse_wheel_events(
+    mut mouse_wheel_events: ResMut<Events<MouseWheel>>,
+    mut mouse_wheel_reader: Local<ManualEventReader<MouseWheel>>,
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut chalk: ResMut<LocalChalk>,
+) {
+    let steps: f32 = mouse_wheel_reader
+        .read(&mouse_wheel_events)
+        .map(|event| wheel_direction(event.y))
+        .sum();
+
+    let ctrl = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+
+    if ctrl {
+        mouse_wheel_events.clear();
+        chalk.0.line_width = wheel_size(chalk.0.line_width, steps);
+    }
+}
+
+fn wheel_direction(y: f32) -> f32 {
+    if y > 0.0 {
+        1.0
+    } else if y < 0.0 {
+        -1.0
+    } else {
+        0.0
+    }
+}
+
+/// Touches currently on the screen. The first one moves the local chalk,
+/// each other one draws with a chalk of its own, so that several people can
+/// draw at once on the same device.
+#[derive(Debug, Resource, Default)]
+struct ActiveTouches {
+    primary: Option<u64>,
+    touches: HashMap<u64, TouchState>,
+}
+
+/// Last position of a touch, in world coordinates
+#[derive(Debug, Clone, Copy)]
+struct TouchState {
+    x: f32,
+    y: f32,
+}
+
+/// A chalk drawn by a secondary touch. Chalks are kept once the touch ends
+/// (`None`), to be reused by the next ones.
+#[derive(Component)]
+pub(crate) struct TouchChalk(pub(crate) Option<u64>);
+
+/// The first touch moves the local chalk in every frame it is held, not only
+/// in the frames with touch events
 fn touch_events(
-    mut touch_evr: EventReader<TouchInput>,
+    mut touch_evr: EventReader<FilteredTouchInput>,
     mut chalk: ResMut<LocalChalk>,
+    mut touches: ResMut<ActiveTouches>,
+    held_touches: Res<Touches>,
+    mut source: ResMut<ChalkSource>,
     camera_q: Query<(&Camera, &GlobalTransform), With<MainCamera>>,
+    bounds: Res<CanvasBounds>,
 ) {
     use bevy::input::touch::TouchPhase;
 
@@ -174,8 +505,41 @@ fn touch_events(
     let mut cursor_position = None;
     let prev_chalk = *chalk;
 
-    for event in &mut touch_evr.read() {
-        cursor_position = Some(event.position);
+    let (camera, camera_transform) = camera_q.single();
+
+    let to_world = |position| {
+        cursor_to_world_position(position, camera, camera_transform)
+            .map(|world_position| bounds.clamp(Vec2::new(world_position[0], -world_position[1])))
+    };
+
+    for FilteredTouchInput(event) in &mut touch_evr.read() {
+        let position = to_world(event.position);
+
+        match event.phase {
+            TouchPhase::Started => {
+                touches.primary.get_or_insert(event.id);
+            }
+            TouchPhase::Moved => {}
+            TouchPhase::Ended | TouchPhase::Canceled => {
+                touches.touches.remove(&event.id);
+            }
+        }
+
+        if let (TouchPhase::Started | TouchPhase::Moved, Some(position)) = (event.phase, position) {
+            touches.touches.insert(
+                event.id,
+                TouchState {
+                    x: position[0],
+                    y: position[1],
+                },
+            );
+        }
+
+        if touches.primary != Some(event.id) {
+            continue;
+        }
+
+        cursor_position = position.or(cursor_position);
         match event.phase {
             TouchPhase::Started => {
                 chalk.just_released = false;
@@ -186,18 +550,23 @@ fn touch_events(
             TouchPhase::Ended | TouchPhase::Canceled => {
                 chalk.pressed = false;
                 press_changed = true;
+                touches.primary = None;
             }
         }
     }
 
-    if let Some(cursor_pos) = cursor_position {
-        let (camera, camera_transform) = camera_q.single();
-        if let Some(world_position) = cursor_to_world_position(cursor_pos, camera, camera_transform)
-        {
-            chalk.x = world_position[0] as i32;
-            chalk.y = -world_position[1] as i32;
-            chalk.updated = is_updated(&prev_chalk, chalk);
-        }
+    if cursor_position.is_none() {
+        cursor_position = touches
+            .primary
+            .and_then(|id| held_touches.get_pressed(id))
+            .and_then(|touch| to_world(touch.position()));
+    }
+
+    if let Some(world_position) = cursor_position {
+        chalk.x = world_position[0];
+        chalk.y = world_position[1];
+        chalk.updated = chalk.is_updated_from(&prev_chalk);
+        *source = ChalkSource::Touch;
     }
 
     if press_changed {
@@ -205,40 +574,175 @@ fn touch_events(
     }
 }
 
-fn is_updated(old_chalk: &Chalk, new_chalk: &Chalk) -> bool {
-    old_chalk.x != new_chalk.x
-        || old_chalk.y != new_chalk.y
-        || old_chalk.pressed != new_chalk.pressed
-        || old_chalk.just_released != new_chalk.just_released
+/// Secondary touches draw with the color and line width of the local chalk.
+/// Their strokes are local only, so they are disabled while collaborating.
+///
+/// Touches reach this after palm rejection: when two touches reporting a
+/// force start within its window, the harder one is taken for a palm and
+/// draws nothing. Touches started further apart draw each with their chalk.
+fn update_touch_chalks(
+    mut commands: Commands,
+    touches: Res<ActiveTouches>,
+    local_chalk: Res<LocalChalk>,
+    mut chalk_q: Query<(Entity, &mut Chalk, &mut TouchChalk)>,
+) {
+    let mut free = Vec::new();
+    let mut assigned = Vec::new();
+
+    for (entity, mut chalk, mut touch_chalk) in &mut chalk_q {
+        // The drawing plugin must see each change only once
+        if chalk.updated || chalk.just_released {
+            chalk.updated = false;
+            chalk.just_released = false;
+        }
+
+        let Some(id) = touch_chalk.0 else {
+            free.push(entity);
+            continue;
+        };
+
+        match touches.touches.get(&id) {
+            Some(touch) if touches.primary != Some(id) => {
+                let prev_chalk = *chalk;
+                chalk.x = touch.x;
+                chalk.y = touch.y;
+                chalk.updated = chalk.is_updated_from(&prev_chalk);
+                assigned.push(id);
+            }
+            _ => {
+                chalk.pressed = false;
+                chalk.just_released = true;
+                touch_chalk.0 = None;
+            }
+        }
+    }
+
+    for (&id, touch) in &touches.touches {
+        if touches.primary == Some(id) || assigned.contains(&id) {
+            continue;
+        }
+
+        let new_chalk = Chalk {
+            pressed: true,
+            updated: true,
+            just_released: false,
+            x: touch.x,
+            y: touch.y,
+            color: local_chalk.0.color,
+            line_width: local_chalk.0.line_width,
+        };
+
+        match free.pop().and_then(|entity| chalk_q.get_mut(entity).ok()) {
+            Some((_, mut chalk, mut touch_chalk)) => {
+                *chalk = new_chalk;
+                touch_chalk.0 = Some(id);
+            }
+            None => {
+                commands.spawn((TouchChalk(Some(id)), make_chalk(new_chalk)));
+            }
+        }
+    }
 }
 
-#[allow(clippy::cast_precision_loss)]
+#[allow(clippy::type_complexity)]
 fn update_cursor(
     mut chalk: ResMut<LocalChalk>,
-    mut cursor_q: Query<(&mut Fill, &mut Transform), With<LocalCursor>>,
+    mut cursor_q: Query<
+        (&mut Stroke, &mut Transform, Option<&BrushSizePulse>),
+        (With<LocalCursor>, Without<LocalCursorDot>),
+    >,
+    mut dot_q: Query<(&mut Fill, &mut Transform), (With<LocalCursorDot>, Without<LocalCursor>)>,
+    easing: Res<EasingConfig>,
 ) {
     let chalk = &mut chalk.0;
-    let (mut fill, mut transform) = cursor_q.single_mut();
+    let translation = Vec3::new(chalk.x, chalk.y, 0.);
 
-    *fill = Fill::color(chalk.color);
+    let (mut stroke, mut transform, pulse) = cursor_q.single_mut();
 
-    let scale = chalk.line_width as f32 / 2.0;
+    // The ring radius is the actual stroke radius, but the ring itself must
+    // be thin, whatever the scale
+    let pulse_scale = pulse.map_or(1.0, |pulse| pulse.scale(*easing));
+    let scale = chalk.line_width / 2.0 * pulse_scale;
     transform.scale = Vec3::new(scale, scale, scale);
+    transform.translation = translation;
+
+    stroke.color = chalk.color.into();
+    stroke.options.line_width = CURSOR_RING_WIDTH / scale;
+
+    let (mut fill, mut transform) = dot_q.single_mut();
 
-    transform.translation = Vec3::new(chalk.x as f32, chalk.y as f32, 0.);
+    *fill = Fill::color(contrast_color(chalk.color));
+
+    // Over the ring
+    transform.translation = translation + Vec3::Z;
+}
+
+fn start_brush_size_pulse(
+    mut commands: Commands,
+    chalk: Res<LocalChalk>,
+    cursor_q: Query<Entity, With<LocalCursor>>,
+    mut last_line_width: Local<Option<f32>>,
+) {
+    let line_width = chalk.0.line_width;
+    let changed = last_line_width.is_some_and(|last| (last - line_width).abs() > f32::EPSILON);
+    *last_line_width = Some(line_width);
+
+    if !changed {
+        return;
+    }
+
+    // A new pulse replaces the running one, if any
+    if let Ok(cursor) = cursor_q.get_single() {
+        commands.entity(cursor).insert(BrushSizePulse::new());
+    }
+}
+
+fn tick_brush_size_pulse(
+    mut commands: Commands,
+    time: Res<Time>,
+    mut pulse_q: Query<(Entity, &mut BrushSizePulse)>,
+) {
+    for (entity, mut pulse) in &mut pulse_q {
+        pulse.timer.tick(time.delta());
+
+        if !pulse.timer.finished() {
+            continue;
+        }
+
+        match pulse.phase {
+            PulsePhase::Growing => {
+                pulse.phase = PulsePhase::Shrinking;
+                pulse.timer.reset();
+            }
+            PulsePhase::Shrinking => {
+                commands.entity(entity).remove::<BrushSizePulse>();
+            }
+        }
+    }
 }
 
 fn update_chalk(
     local_chalk: Res<LocalChalk>,
     mut chalk_q: Query<&mut Chalk, With<LocalChalkFlag>>,
 ) {
+    // Input systems take LocalChalk mutably every frame, so the resource is
+    // always marked changed. Compare the values, to trigger Changed<Chalk>
+    // only when the chalk actually changed.
     if let Ok(mut chalk) = chalk_q.get_single_mut() {
-        *chalk = local_chalk.0;
+        chalk.set_if_neq(local_chalk.0);
     }
 }
 
 const COLORS: [Srgba; 7] = [WHITE, BLUE, TURQUOISE, GREEN, YELLOW, ORANGE, RED];
 
+/// Size bounds for the mouse wheel, keyboard, toolbar and programmatic
+/// changes
+const MIN_LINE_WIDTH: f32 = 0.5;
+const MAX_LINE_WIDTH: f32 = 100.0;
+
+/// Mouse wheel allows finer size changes than keyboard and toolbar
+const WHEEL_SIZE_STEP: f32 = 0.5;
+
 fn next_color(curr_color: Srgba) -> Srgba {
     if let Some(next_color) = COLORS
         .iter()
@@ -252,12 +756,57 @@ fn next_color(curr_color: Srgba) -> Srgba {
     }
 }
 
-fn incr_size(size: u32) -> u32 {
-    min(100, size * 2)
+fn wheel_size(size: f32, steps: f32) -> f32 {
+    (size + steps * WHEEL_SIZE_STEP).clamp(MIN_LINE_WIDTH, MAX_LINE_WIDTH)
+}
+
+/// How keyboard and toolbar change the chalk size
+#[derive(Resource, Debug, Clone, Copy, Default)]
+pub(crate) struct SizeStepConfig {
+    mode: SizeStepMode,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub(crate) enum SizeStepMode {
+    /// Double or halve the size
+    #[default]
+    Exponential,
+    /// Add or subtract `step` pixels
+    Linear { step: u32 },
+}
+
+/// Step used when switching to linear mode
+const DEFAULT_LINEAR_STEP: u32 = 2;
+
+impl SizeStepConfig {
+    fn toggle_mode(&mut self) {
+        self.mode = match self.mode {
+            SizeStepMode::Exponential => SizeStepMode::Linear {
+                step: DEFAULT_LINEAR_STEP,
+            },
+            SizeStepMode::Linear { .. } => SizeStepMode::Exponential,
+        };
+    }
 }
 
-fn decr_size(size: u32) -> u32 {
-    max(1, size / 2)
+#[allow(clippy::cast_precision_loss)]
+fn incr_size(size: f32, config: &SizeStepConfig) -> f32 {
+    let size = match config.mode {
+        SizeStepMode::Exponential => size * 2.0,
+        SizeStepMode::Linear { step } => size + step as f32,
+    };
+
+    size.clamp(MIN_LINE_WIDTH, MAX_LINE_WIDTH)
+}
+
+#[allow(clippy::cast_precision_loss)]
+fn decr_size(size: f32, config: &SizeStepConfig) -> f32 {
+    let size = match config.mode {
+        SizeStepMode::Exponential => size / 2.0,
+        SizeStepMode::Linear { step } => size - step as f32,
+    };
+
+    size.clamp(MIN_LINE_WIDTH, MAX_LINE_WIDTH)
 }
 
 impl LocalChalk {
@@ -268,25 +817,58 @@ impl LocalChalk {
         self.0.color = next_color(self.0.color);
         self.0.color
     }
-    pub(crate) fn grow(&mut self) -> u32 {
-        self.0.line_width = incr_size(self.0.line_width);
-        self.0.line_width
+    pub(crate) fn set_color(&mut self, color: Srgba) {
+        self.0.color = color;
+    }
+    /// Pick a palette color by index, out of range indices are ignored
+    pub(crate) fn select_color(&mut self, index: usize) -> Srgba {
+        if let Some(&color) = COLORS.get(index) {
+            self.set_color(color);
+        }
+        self.0.color
     }
-    pub(crate) fn shrink(&mut self) -> u32 {
-        self.0.line_width = decr_size(self.0.line_width);
+    /// Exact line width, the mouse wheel may leave it between whole pixels
+    pub(crate) fn line_width(&self) -> f32 {
         self.0.line_width
     }
+    /// Set the line width, clamped to the allowed range
+    #[allow(clippy::cast_precision_loss)]
+    pub(crate) fn set_line_width(&mut self, width: u32) {
+        self.0.line_width = (width as f32).clamp(MIN_LINE_WIDTH, MAX_LINE_WIDTH);
+    }
+    pub(crate) fn grow(&mut self, config: &SizeStepConfig) -> f32 {
+        self.0.line_width = incr_size(self.line_width(), config);
+        self.line_width()
+    }
+    pub(crate) fn shrink(&mut self, config: &SizeStepConfig) -> f32 {
+        self.0.line_width = decr_size(self.line_width(), config);
+        self.line_width()
+    }
 }
 
 #[derive(Event)]
 pub(crate) struct ChangeColorEvent;
 
+/// Select the palette color at the given index
+#[derive(Event)]
+pub(crate) struct SelectColorEvent(pub usize);
+
 #[derive(Event)]
 pub(crate) struct GrowEvent;
 
 #[derive(Event)]
 pub(crate) struct ShrinkEvent;
 
+#[derive(Event)]
+pub(crate) struct UndoEvent;
+
+/// A stroke of the local chalk was undone, peers must undo it too
+#[derive(Event)]
+pub(crate) struct StrokeUndoneEvent;
+
+#[derive(Event)]
+pub(crate) struct ToggleSizeStepModeEvent;
+
 fn handle_change_color_event(
     mut events: EventReader<ChangeColorEvent>,
     mut chalk: ResMut<LocalChalk>,
@@ -296,14 +878,376 @@ fn handle_change_color_event(
     }
 }
 
-fn handle_incr_size_event(mut events: EventReader<GrowEvent>, mut chalk: ResMut<LocalChalk>) {
+fn handle_select_color_event(
+    mut events: EventReader<SelectColorEvent>,
+    mut chalk: ResMut<LocalChalk>,
+) {
+    for SelectColorEvent(index) in &mut events.read() {
+        chalk.select_color(*index);
+    }
+}
+
+/// Drive the local chalk programmatically, instead of with mouse or touch
+#[derive(Event, Debug, Clone, Copy)]
+pub enum DrawEvent {
+    /// Move without drawing, ending the current stroke if any
+    MoveTo {
+        x: f32,
+        y: f32,
+    },
+    /// Draw a line from the current position
+    LineTo {
+        x: f32,
+        y: f32,
+    },
+    StrokeEnd,
+    SetColor(Color),
+    SetWidth(u32),
+}
+
+/// Events are applied in order. The drawing plugin only sees the chalk once
+/// per frame, so events are applied up to the first one moving the chalk,
+/// and the remaining ones are kept for the next frames. The mouse takes the
+/// chalk back once the queue is empty and the cursor moves.
+fn handle_draw_event(
+    mut events: EventReader<DrawEvent>,
+    mut chalk: ResMut<LocalChalk>,
+    mut queue: ResMut<DrawQueue>,
+    mut source: ResMut<ChalkSource>,
+) {
+    let queue = &mut queue.0;
+    queue.extend(events.read().copied());
+
+    while let Some(event) = queue.pop_front() {
+        let (x, y, pressed) = match event {
+            DrawEvent::MoveTo { x, y } => (x, y, false),
+            DrawEvent::LineTo { x, y } => (x, y, true),
+            DrawEvent::StrokeEnd => (chalk.0.x, chalk.0.y, false),
+            DrawEvent::SetColor(color) => {
+                chalk.set_color(color.into());
+                continue;
+            }
+            DrawEvent::SetWidth(width) => {
+                chalk.set_line_width(width);
+                continue;
+            }
+        };
+
+        let chalk = &mut chalk.0;
+        let prev_chalk = *chalk;
+
+        chalk.x = x;
+        chalk.y = y;
+        chalk.pressed = pressed;
+        chalk.just_released = prev_chalk.pressed && !pressed;
+        chalk.updated = chalk.is_updated_from(&prev_chalk);
+        *source = ChalkSource::Script;
+
+        break;
+    }
+}
+
+fn handle_incr_size_event(
+    mut events: EventReader<GrowEvent>,
+    mut chalk: ResMut<LocalChalk>,
+    config: Res<SizeStepConfig>,
+) {
     for _ in &mut events.read() {
-        chalk.grow();
+        chalk.grow(&config);
     }
 }
 
-fn handle_decr_size_event(mut events: EventReader<ShrinkEvent>, mut chalk: ResMut<LocalChalk>) {
+fn handle_decr_size_event(
+    mut events: EventReader<ShrinkEvent>,
+    mut chalk: ResMut<LocalChalk>,
+    config: Res<SizeStepConfig>,
+) {
     for _ in &mut events.read() {
-        chalk.shrink();
+        chalk.shrink(&config);
+    }
+}
+
+fn handle_toggle_size_step_mode_event(
+    mut events: EventReader<ToggleSizeStepModeEvent>,
+    mut config: ResMut<SizeStepConfig>,
+) {
+    for _ in &mut events.read() {
+        config.toggle_mode();
+    }
+}
+
+fn handle_undo_event(
+    mut commands: Commands,
+    mut events: EventReader<UndoEvent>,
+    mut stroke_undone_events: EventWriter<StrokeUndoneEvent>,
+    mut history_q: Query<&mut History, With<LocalChalkFlag>>,
+    ghost_q: Query<&EraserGhost>,
+) {
+    let Ok(mut history) = history_q.get_single_mut() else {
+        return;
+    };
+
+    // Undo brings back the strokes just erased instead, see unerase_ghosts
+    if ghost_q.iter().any(EraserGhost::is_revivable) {
+        events.clear();
+        return;
+    }
+
+    for _ in &mut events.read() {
+        if history.undo(&mut commands) == Some(Undone::Stroke) {
+            stroke_undone_events.send(StrokeUndoneEvent);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bevy::ecs::system::RunSystemOnce;
+
+    const EXPONENTIAL: SizeStepConfig = SizeStepConfig {
+        mode: SizeStepMode::Exponential,
+    };
+
+    const LINEAR: SizeStepConfig = SizeStepConfig {
+        mode: SizeStepMode::Linear { step: 2 },
+    };
+
+    fn assert_width(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-5, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn exponential_shrink_stops_at_min_width() {
+        assert_width(decr_size(MIN_LINE_WIDTH, &EXPONENTIAL), MIN_LINE_WIDTH);
+        assert_width(decr_size(1.0, &EXPONENTIAL), MIN_LINE_WIDTH);
+        assert_width(decr_size(0.75, &EXPONENTIAL), MIN_LINE_WIDTH);
+        assert_width(decr_size(3.0, &EXPONENTIAL), 1.5);
+    }
+
+    #[test]
+    fn exponential_grow_stops_at_max_width() {
+        assert_width(incr_size(MAX_LINE_WIDTH, &EXPONENTIAL), MAX_LINE_WIDTH);
+        assert_width(incr_size(64.0, &EXPONENTIAL), MAX_LINE_WIDTH);
+        assert_width(incr_size(50.0, &EXPONENTIAL), MAX_LINE_WIDTH);
+        assert_width(incr_size(49.0, &EXPONENTIAL), 98.0);
+    }
+
+    #[test]
+    fn linear_shrink_stops_at_min_width() {
+        assert_width(decr_size(MIN_LINE_WIDTH, &LINEAR), MIN_LINE_WIDTH);
+        assert_width(decr_size(2.0, &LINEAR), MIN_LINE_WIDTH);
+        assert_width(decr_size(2.5, &LINEAR), MIN_LINE_WIDTH);
+        assert_width(decr_size(3.0, &LINEAR), 1.0);
+    }
+
+    #[test]
+    fn linear_grow_stops_at_max_width() {
+        assert_width(incr_size(MAX_LINE_WIDTH, &LINEAR), MAX_LINE_WIDTH);
+        assert_width(incr_size(99.0, &LINEAR), MAX_LINE_WIDTH);
+        assert_width(incr_size(98.0, &LINEAR), MAX_LINE_WIDTH);
+        assert_width(incr_size(97.0, &LINEAR), 99.0);
+    }
+
+    #[test]
+    fn widths_out_of_range_are_brought_back() {
+        for config in [EXPONENTIAL, LINEAR] {
+            assert!(incr_size(0.0, &config) >= MIN_LINE_WIDTH);
+            assert_width(decr_size(0.0, &config), MIN_LINE_WIDTH);
+            assert_width(incr_size(MAX_LINE_WIDTH + 1.0, &config), MAX_LINE_WIDTH);
+            assert_width(incr_size(f32::MAX, &config), MAX_LINE_WIDTH);
+            assert!(decr_size(MAX_LINE_WIDTH + 1.0, &config) <= MAX_LINE_WIDTH);
+        }
+    }
+
+    #[test]
+    fn growing_from_half_a_pixel_steps_from_the_exact_width() {
+        let mut chalk = LocalChalk::default();
+        chalk.0.line_width = MIN_LINE_WIDTH;
+
+        assert_width(chalk.grow(&EXPONENTIAL), 1.0);
+
+        chalk.0.line_width = MIN_LINE_WIDTH;
+        assert_width(chalk.grow(&LINEAR), 2.5);
+    }
+
+    #[test]
+    fn shrinking_half_a_pixel_keeps_the_minimum() {
+        let mut chalk = LocalChalk::default();
+        chalk.0.line_width = MIN_LINE_WIDTH;
+
+        assert_width(chalk.shrink(&EXPONENTIAL), MIN_LINE_WIDTH);
+        assert_width(chalk.shrink(&LINEAR), MIN_LINE_WIDTH);
+    }
+
+    #[test]
+    fn whole_pixel_widths_are_clamped_to_the_same_bounds() {
+        let mut chalk = LocalChalk::default();
+
+        chalk.set_line_width(0);
+        assert_width(chalk.0.line_width, MIN_LINE_WIDTH);
+
+        chalk.set_line_width(1000);
+        assert_width(chalk.0.line_width, MAX_LINE_WIDTH);
+    }
+
+    #[test]
+    fn selecting_a_palette_color_sets_the_chalk_color() {
+        let mut chalk = LocalChalk::default();
+
+        assert_eq!(chalk.select_color(2), COLORS[2]);
+        assert_eq!(chalk.color(), COLORS[2]);
+    }
+
+    #[test]
+    fn selecting_past_the_palette_keeps_the_color() {
+        let mut chalk = LocalChalk::default();
+        chalk.select_color(1);
+
+        assert_eq!(chalk.select_color(COLORS.len()), COLORS[1]);
+    }
+
+    fn mouse_world(pressed: bool) -> World {
+        let mut world = World::new();
+        world.init_resource::<Events<MouseButtonInput>>();
+        world.init_resource::<Events<CursorLeft>>();
+        world.init_resource::<ButtonInput<KeyCode>>();
+        world.init_resource::<LocalChalk>();
+        world.resource_mut::<LocalChalk>().0.pressed = pressed;
+        world
+    }
+
+    #[test]
+    fn leaving_the_window_releases_the_chalk() {
+        let mut world = mouse_world(true);
+        world.send_event(CursorLeft {
+            window: Entity::PLACEHOLDER,
+        });
+
+        world.run_system_once(mouse_events);
+
+        let chalk = world.resource::<LocalChalk>().0;
+        assert!(!chalk.pressed);
+        assert!(chalk.just_released);
+    }
+
+    #[test]
+    fn leaving_the_window_without_pressing_changes_nothing() {
+        let mut world = mouse_world(false);
+        world.send_event(CursorLeft {
+            window: Entity::PLACEHOLDER,
+        });
+
+        world.run_system_once(mouse_events);
+
+        let chalk = world.resource::<LocalChalk>().0;
+        assert!(!chalk.pressed);
+        assert!(!chalk.just_released);
+    }
+
+    // Positions given to cursor_to_world_position are in logical pixels, so
+    // moving the window to a monitor with another scale factor moves nothing
+    #[test]
+    fn cursor_position_does_not_depend_on_the_scale_factor() {
+        let mut window = Window::default();
+
+        window.resolution.set_scale_factor(2.0);
+        window.set_physical_cursor_position(Some(bevy::math::DVec2::new(200.0, 100.0)));
+        let at_2x = window.cursor_position();
+
+        window.resolution.set_scale_factor(1.0);
+        window.set_physical_cursor_position(Some(bevy::math::DVec2::new(100.0, 50.0)));
+        let at_1x = window.cursor_position();
+
+        assert_eq!(at_2x, Some(Vec2::new(100.0, 50.0)));
+        assert_eq!(at_1x, at_2x);
+    }
+
+    fn chalk_changed_by_update(local_chalk: Chalk) -> bool {
+        let mut world = World::new();
+        world.insert_resource(LocalChalk(local_chalk));
+        let entity = world.spawn((LocalChalkFlag, LocalChalk::default().0)).id();
+        world.clear_trackers();
+
+        world.run_system_once(update_chalk);
+
+        world
+            .entity(entity)
+            .get_ref::<Chalk>()
+            .unwrap()
+            .is_changed()
+    }
+
+    #[test]
+    fn unchanged_local_chalk_does_not_change_the_chalk() {
+        assert!(!chalk_changed_by_update(LocalChalk::default().0));
+    }
+
+    #[test]
+    fn moved_local_chalk_changes_the_chalk() {
+        let mut chalk = LocalChalk::default().0;
+        chalk.x += 1.0;
+
+        assert!(chalk_changed_by_update(chalk));
+    }
+
+    fn draw(events: &[DrawEvent]) -> World {
+        let mut world = World::new();
+        world.init_resource::<Events<DrawEvent>>();
+        world.init_resource::<LocalChalk>();
+        world.init_resource::<DrawQueue>();
+        world.init_resource::<ChalkSource>();
+
+        for &event in events {
+            world.send_event(event);
+        }
+
+        world.run_system_once(handle_draw_event);
+        world
+    }
+
+    #[test]
+    fn drawing_events_take_the_chalk_from_the_mouse() {
+        let world = draw(&[DrawEvent::LineTo { x: 1.0, y: 2.0 }]);
+
+        let chalk = world.resource::<LocalChalk>().0;
+        assert!(chalk.pressed);
+        assert_eq!((chalk.x, chalk.y), (1.0, 2.0));
+        assert_eq!(*world.resource::<ChalkSource>(), ChalkSource::Script);
+    }
+
+    #[test]
+    fn drawing_events_are_applied_one_move_per_frame() {
+        let world = draw(&[
+            DrawEvent::MoveTo { x: 1.0, y: 2.0 },
+            DrawEvent::LineTo { x: 3.0, y: 4.0 },
+        ]);
+
+        assert_eq!(world.resource::<DrawQueue>().0.len(), 1);
+    }
+
+    fn undo_once(history: History) -> usize {
+        let mut world = World::new();
+        world.init_resource::<Events<UndoEvent>>();
+        world.init_resource::<Events<StrokeUndoneEvent>>();
+        world.spawn((LocalChalkFlag, history));
+        world.send_event(UndoEvent);
+
+        world.run_system_once(handle_undo_event);
+
+        world.resource::<Events<StrokeUndoneEvent>>().len()
+    }
+
+    #[test]
+    fn undoing_a_flip_is_not_sent_to_peers() {
+        let mut history = History::default();
+        history.push_polylines(Vec::new());
+
+        assert_eq!(undo_once(history), 0);
+    }
+
+    #[test]
+    fn undoing_nothing_is_not_sent_to_peers() {
+        assert_eq!(undo_once(History::default()), 0);
     }
 }
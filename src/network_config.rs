@@ -0,0 +1,96 @@
+use std::path::PathBuf;
+
+use serde::Deserialize;
+
+use crate::collab::IceServer;
+
+/// Network options which are not practical to pass on the command line, read
+/// from `lavagna/network.toml` in the user configuration directory.
+#[derive(Debug, Default, Deserialize)]
+pub(crate) struct WebRtcConfig {
+    /// Used when `--collab-url` is not given
+    pub(crate) signaling_url: Option<String>,
+    pub(crate) ice_server: Option<IceServer>,
+}
+
+/// STUN and TURN, plain or over TLS
+const ICE_SCHEMES: [&str; 4] = ["stun:", "stuns:", "turn:", "turns:"];
+
+impl WebRtcConfig {
+    fn validate(self) -> Result<Self, String> {
+        let Some(ice_server) = &self.ice_server else {
+            return Ok(self);
+        };
+
+        let valid = ice_server
+            .urls
+            .iter()
+            .any(|url| ICE_SCHEMES.iter().any(|scheme| url.starts_with(scheme)));
+
+        if valid {
+            Ok(self)
+        } else {
+            Err("ICE server urls must start with stun:, stuns:, turn: or turns:".to_owned())
+        }
+    }
+}
+
+fn config_path() -> Option<PathBuf> {
+    Some(dirs::config_dir()?.join("lavagna").join("network.toml"))
+}
+
+/// A missing file is not an error, defaults are used. An invalid file is
+/// returned as an error, to be reported once logging is initialized.
+pub(crate) fn load() -> Result<WebRtcConfig, String> {
+    let Some(path) = config_path() else {
+        return Ok(WebRtcConfig::default());
+    };
+
+    let Ok(content) = std::fs::read_to_string(&path) else {
+        return Ok(WebRtcConfig::default());
+    };
+
+    toml::from_str::<WebRtcConfig>(&content)
+        .map_err(ToString::to_string)
+        .and_then(WebRtcConfig::validate)
+        .map_err(|err| format!("ignoring {}: {err}", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn with_urls(urls: &[&str]) -> WebRtcConfig {
+        WebRtcConfig {
+            signaling_url: None,
+            ice_server: Some(IceServer {
+                urls: urls.iter().map(ToString::to_string).collect(),
+                username: None,
+                credential: None,
+            }),
+        }
+    }
+
+    #[test]
+    fn stun_and_turn_urls_are_valid() {
+        for url in [
+            "stun:stun.example.org",
+            "stuns:stun.example.org",
+            "turn:turn.example.org",
+            "turns:turn.example.org:443",
+        ] {
+            assert!(with_urls(&[url]).validate().is_ok(), "{url}");
+        }
+    }
+
+    #[test]
+    fn other_urls_are_invalid() {
+        assert!(with_urls(&["https://turn.example.org"]).validate().is_err());
+        assert!(with_urls(&[]).validate().is_err());
+    }
+
+    #[test]
+    fn no_ice_server_is_valid() {
+        assert!(WebRtcConfig::default().validate().is_ok());
+    }
+}
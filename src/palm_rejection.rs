@@ -0,0 +1,281 @@
+#![allow(clippy::needless_pass_by_value)]
+
+use bevy::input::touch::{ForceTouch, TouchPhase};
+use bevy::prelude::*;
+use bevy::utils::{Duration, HashMap, Instant};
+
+/// Filter touch inputs, dropping the ones which look like a palm resting on
+/// the screen. Filtered touches are forwarded as [`FilteredTouchInput`].
+pub(crate) struct PalmRejectionPlugin;
+
+impl Plugin for PalmRejectionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PalmRejectionConfig>()
+            .init_resource::<PalmRejection>()
+            .add_event::<FilteredTouchInput>()
+            .add_systems(PreUpdate, reject_palms);
+    }
+}
+
+/// Two touches starting within `window` are considered a stylus (or finger)
+/// and a palm landing together.
+#[derive(Debug, Resource, Clone, Copy)]
+pub(crate) struct PalmRejectionConfig {
+    window: Duration,
+}
+
+impl Default for PalmRejectionConfig {
+    fn default() -> Self {
+        Self {
+            window: Duration::from_millis(50),
+        }
+    }
+}
+
+/// A touch input which is not considered a palm
+#[derive(Event, Debug, Clone, Copy)]
+pub(crate) struct FilteredTouchInput(pub TouchInput);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TouchStatus {
+    /// Just started, it is not yet known if it is a palm
+    Pending,
+    /// Not a palm, events are forwarded
+    Accepted,
+    /// A palm, events are dropped
+    Rejected,
+}
+
+#[derive(Debug)]
+struct TouchState {
+    started_at: Instant,
+    first: TouchInput,
+    last: TouchInput,
+    size: Option<f32>,
+    status: TouchStatus,
+}
+
+impl TouchState {
+    fn new(event: TouchInput, now: Instant) -> Self {
+        Self {
+            started_at: now,
+            first: event,
+            last: event,
+            size: contact_size(&event),
+            status: TouchStatus::Pending,
+        }
+    }
+}
+
+/// Touches tracked by id. A touch stays pending for the configured window,
+/// so that a palm landing just after the stylus can still be detected.
+#[derive(Debug, Resource, Default)]
+struct PalmRejection {
+    touches: HashMap<u64, TouchState>,
+}
+
+impl PalmRejection {
+    /// Track a new touch, returning its status. Touches without force data
+    /// can't be compared, so they are accepted right away.
+    fn start(
+        &mut self,
+        event: TouchInput,
+        now: Instant,
+        config: &PalmRejectionConfig,
+    ) -> TouchStatus {
+        let mut new_touch = TouchState::new(event, now);
+
+        let Some(new_size) = new_touch.size else {
+            new_touch.status = TouchStatus::Accepted;
+            self.touches.insert(event.id, new_touch);
+            return TouchStatus::Accepted;
+        };
+
+        for touch in self.touches.values_mut() {
+            let simultaneous = now.duration_since(touch.started_at) < config.window;
+
+            if !simultaneous || touch.status != TouchStatus::Pending {
+                continue;
+            }
+
+            let Some(size) = touch.size else {
+                continue;
+            };
+
+            // Platforms don't report the contact area, but a palm usually
+            // presses harder than a stylus. On a tie the first touch is kept.
+            if size > new_size {
+                touch.status = TouchStatus::Rejected;
+            } else {
+                new_touch.status = TouchStatus::Rejected;
+            }
+        }
+
+        let status = new_touch.status;
+        self.touches.insert(event.id, new_touch);
+        status
+    }
+
+    /// Accept touches which have been pending for long enough, returning
+    /// the events needed to catch up with them
+    fn accept_expired(&mut self, now: Instant, config: &PalmRejectionConfig) -> Vec<TouchInput> {
+        let mut events = Vec::new();
+
+        for touch in self.touches.values_mut() {
+            let expired = now.duration_since(touch.started_at) >= config.window;
+
+            if touch.status == TouchStatus::Pending && expired {
+                touch.status = TouchStatus::Accepted;
+                events.extend(catch_up(touch));
+            }
+        }
+
+        events
+    }
+}
+
+/// Events needed to replay a pending touch: where it started and where it is now
+fn catch_up(touch: &TouchState) -> Vec<TouchInput> {
+    let mut events = vec![touch.first];
+
+    if touch.last.phase != TouchPhase::Started {
+        events.push(touch.last);
+    }
+
+    events
+}
+
+/// Normalized force of the touch, if the platform reports one
+#[allow(clippy::cast_possible_truncation)]
+fn contact_size(event: &TouchInput) -> Option<f32> {
+    match event.force? {
+        ForceTouch::Calibrated {
+            force,
+            max_possible_force,
+            ..
+        } => Some((force / max_possible_force) as f32),
+        ForceTouch::Normalized(force) => Some(force as f32),
+    }
+}
+
+fn reject_palms(
+    mut touch_evr: EventReader<TouchInput>,
+    mut filtered_evw: EventWriter<FilteredTouchInput>,
+    mut palm_rejection: ResMut<PalmRejection>,
+    config: Res<PalmRejectionConfig>,
+) {
+    let now = Instant::now();
+
+    for &event in touch_evr.read() {
+        if event.phase == TouchPhase::Started {
+            if palm_rejection.start(event, now, &config) == TouchStatus::Accepted {
+                filtered_evw.send(FilteredTouchInput(event));
+            }
+            continue;
+        }
+
+        let ended = matches!(event.phase, TouchPhase::Ended | TouchPhase::Canceled);
+
+        let Some(touch) = palm_rejection.touches.get_mut(&event.id) else {
+            continue;
+        };
+
+        match touch.status {
+            TouchStatus::Pending if ended => {
+                // A quick tap, shorter than the window
+                filtered_evw.send(FilteredTouchInput(touch.first));
+                filtered_evw.send(FilteredTouchInput(event));
+            }
+            TouchStatus::Pending => touch.last = event,
+            TouchStatus::Accepted => {
+                filtered_evw.send(FilteredTouchInput(event));
+            }
+            TouchStatus::Rejected => {}
+        }
+
+        if ended {
+            palm_rejection.touches.remove(&event.id);
+        }
+    }
+
+    let accepted = palm_rejection.accept_expired(now, &config);
+    filtered_evw.send_batch(accepted.into_iter().map(FilteredTouchInput));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn started(id: u64, force: Option<f64>) -> TouchInput {
+        TouchInput {
+            phase: TouchPhase::Started,
+            position: Vec2::ZERO,
+            window: Entity::PLACEHOLDER,
+            force: force.map(ForceTouch::Normalized),
+            id,
+        }
+    }
+
+    fn status(palm_rejection: &PalmRejection, id: u64) -> TouchStatus {
+        palm_rejection.touches[&id].status
+    }
+
+    #[test]
+    fn touches_without_force_are_accepted_right_away() {
+        let config = PalmRejectionConfig::default();
+        let mut palm_rejection = PalmRejection::default();
+        let now = Instant::now();
+
+        assert_eq!(
+            palm_rejection.start(started(1, None), now, &config),
+            TouchStatus::Accepted
+        );
+        assert_eq!(
+            palm_rejection.start(started(2, None), now, &config),
+            TouchStatus::Accepted
+        );
+    }
+
+    #[test]
+    fn the_harder_of_two_simultaneous_touches_is_rejected() {
+        let config = PalmRejectionConfig::default();
+        let mut palm_rejection = PalmRejection::default();
+        let now = Instant::now();
+
+        palm_rejection.start(started(1, Some(0.9)), now, &config);
+        palm_rejection.start(started(2, Some(0.2)), now, &config);
+
+        assert_eq!(status(&palm_rejection, 1), TouchStatus::Rejected);
+        assert_eq!(status(&palm_rejection, 2), TouchStatus::Pending);
+    }
+
+    #[test]
+    fn on_a_tie_the_first_touch_is_kept() {
+        let config = PalmRejectionConfig::default();
+        let mut palm_rejection = PalmRejection::default();
+        let now = Instant::now();
+
+        palm_rejection.start(started(1, Some(0.5)), now, &config);
+        palm_rejection.start(started(2, Some(0.5)), now, &config);
+
+        assert_eq!(status(&palm_rejection, 1), TouchStatus::Pending);
+        assert_eq!(status(&palm_rejection, 2), TouchStatus::Rejected);
+    }
+
+    #[test]
+    fn pending_touches_are_accepted_after_the_window() {
+        let config = PalmRejectionConfig::default();
+        let mut palm_rejection = PalmRejection::default();
+        let now = Instant::now();
+
+        palm_rejection.start(started(1, Some(0.5)), now, &config);
+
+        assert!(palm_rejection.accept_expired(now, &config).is_empty());
+
+        let later = now + config.window;
+        let events = palm_rejection.accept_expired(later, &config);
+
+        assert_eq!(events.len(), 1);
+        assert_eq!(status(&palm_rejection, 1), TouchStatus::Accepted);
+    }
+}
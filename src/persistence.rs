@@ -0,0 +1,115 @@
+use bevy::ecs::entity::EntityHashMap;
+use bevy::ecs::schedule::common_conditions::on_event;
+use bevy::prelude::*;
+use bevy::scene::serde::SceneDeserializer;
+use serde::de::DeserializeSeed;
+
+use crate::collab::Room;
+use crate::drawing::{Completed, History, Polyline, StrokeMeta};
+use crate::Chalk;
+
+/// Blackboard is saved to (and loaded from) this file, in the working directory
+const SCENE_PATH: &str = "lavagna.scn.ron";
+
+/// Save completed strokes to a bevy scene and load them back
+pub(crate) struct ScenePersistencePlugin;
+
+impl Plugin for ScenePersistencePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<SaveSceneEvent>()
+            .add_event::<LoadSceneEvent>()
+            .add_systems(Update, save_scene.run_if(on_event::<SaveSceneEvent>()))
+            .add_systems(Update, load_scene.run_if(on_event::<LoadSceneEvent>()));
+    }
+}
+
+#[derive(Event)]
+pub(crate) struct SaveSceneEvent;
+
+#[derive(Event)]
+pub(crate) struct LoadSceneEvent;
+
+fn save_scene(world: &mut World) {
+    let mut strokes = world.query_filtered::<Entity, With<Completed>>();
+
+    let scene = DynamicSceneBuilder::from_world(world)
+        .allow::<Completed>()
+        .allow::<Polyline>()
+        .allow::<Chalk>()
+        .allow::<StrokeMeta>()
+        .extract_entities(strokes.iter(world))
+        .build();
+
+    let type_registry = world.resource::<AppTypeRegistry>().read();
+
+    let serialized = match scene.serialize(&type_registry) {
+        Ok(serialized) => serialized,
+        Err(err) => {
+            log::error!("cannot serialize scene: {err}");
+            return;
+        }
+    };
+
+    match std::fs::write(SCENE_PATH, serialized) {
+        Ok(()) => log::info!("scene saved to {SCENE_PATH}"),
+        Err(err) => log::error!("cannot write {SCENE_PATH}: {err}"),
+    }
+}
+
+fn load_scene(world: &mut World) {
+    // Peers would keep their own blackboard, loaded strokes are never sent
+    if world.contains_resource::<Room>() {
+        log::warn!("cannot load {SCENE_PATH} while collaborating");
+        return;
+    }
+
+    let serialized = match std::fs::read_to_string(SCENE_PATH) {
+        Ok(serialized) => serialized,
+        Err(err) => {
+            log::error!("cannot read {SCENE_PATH}: {err}");
+            return;
+        }
+    };
+
+    let mut deserializer = match ron::de::Deserializer::from_str(&serialized) {
+        Ok(deserializer) => deserializer,
+        Err(err) => {
+            log::error!("cannot parse {SCENE_PATH}: {err}");
+            return;
+        }
+    };
+
+    let scene = {
+        let type_registry = world.resource::<AppTypeRegistry>().read();
+        let scene_deserializer = SceneDeserializer {
+            type_registry: &type_registry,
+        };
+        scene_deserializer.deserialize(&mut deserializer)
+    };
+
+    let scene = match scene {
+        Ok(scene) => scene,
+        Err(err) => {
+            log::error!("cannot deserialize {SCENE_PATH}: {err}");
+            return;
+        }
+    };
+
+    // The loaded scene replaces the blackboard
+    let mut strokes = world.query_filtered::<Entity, With<Completed>>();
+    let strokes: Vec<Entity> = strokes.iter(world).collect();
+    // Selection outlines are children of the strokes
+    for stroke in strokes {
+        world.entity_mut(stroke).despawn_recursive();
+    }
+
+    for mut history in world.query::<&mut History>().iter_mut(world) {
+        history.clear();
+    }
+
+    // Meshes are generated by the drawing plugin, see `restore_strokes`
+    match scene.write_to_world(world, &mut EntityHashMap::default()) {
+        Ok(()) => log::info!("scene loaded from {SCENE_PATH}"),
+        Err(err) => log::error!("cannot spawn scene: {err}"),
+    }
+}
@@ -0,0 +1,329 @@
+#![allow(clippy::needless_pass_by_value)]
+
+use bevy::prelude::*;
+use bevy::utils::Duration;
+#[cfg(feature = "lavagna_serde")]
+use serde::{Deserialize, Serialize};
+
+use crate::drawing::{self, make_chalk, ClearEvent, ClearTarget, Polyline};
+use crate::local_chalk::LocalChalk;
+use crate::Chalk;
+
+/// Recordings are saved to (and loaded from) this file, in the working directory
+#[cfg(feature = "lavagna_serde")]
+const RECORDING_PATH: &str = "lavagna.lrec";
+
+const MIN_PLAYBACK_SPEED: f32 = 0.25;
+const MAX_PLAYBACK_SPEED: f32 = 4.0;
+
+/// Record the local drawing session and replay it later, like a lecture.
+/// This is independent of undo: the replay shows what was drawn, as it
+/// was drawn.
+pub(crate) struct RecordingPlugin;
+
+impl Plugin for RecordingPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RecordingState>()
+            .init_resource::<PlaybackSpeed>()
+            .add_event::<ToggleRecordingEvent>()
+            .add_event::<StartPlaybackEvent>()
+            .add_event::<ChangePlaybackSpeedEvent>()
+            .add_systems(Update, handle_toggle_recording_event)
+            .add_systems(Update, handle_start_playback_event)
+            .add_systems(Update, handle_change_playback_speed_event)
+            .add_systems(Update, record)
+            // Each replayed chalk state must reach the drawing in the same
+            // frame, before the next one overwrites it
+            .add_systems(Update, play.before(drawing::update));
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "lavagna_serde", derive(Serialize, Deserialize))]
+enum DrawCommand {
+    /// New state of the local chalk
+    Chalk(Chalk),
+    Clear(ClearTarget),
+}
+
+#[derive(Debug, Clone, Copy)]
+#[cfg_attr(feature = "lavagna_serde", derive(Serialize, Deserialize))]
+struct TimedEvent {
+    /// Milliseconds since the recording started
+    elapsed_ms: u64,
+    command: DrawCommand,
+}
+
+#[derive(Debug, Resource, Default)]
+pub(crate) struct RecordingState {
+    recording: bool,
+    started_at: Duration,
+    events: Vec<TimedEvent>,
+    last_chalk: Option<Chalk>,
+}
+
+impl RecordingState {
+    fn start(&mut self, now: Duration) {
+        self.recording = true;
+        self.started_at = now;
+        self.events.clear();
+        self.last_chalk = None;
+    }
+
+    #[allow(clippy::cast_possible_truncation)]
+    fn push(&mut self, now: Duration, command: DrawCommand) {
+        let elapsed_ms = now.saturating_sub(self.started_at).as_millis() as u64;
+        self.events.push(TimedEvent {
+            elapsed_ms,
+            command,
+        });
+    }
+}
+
+/// Playback speed multiplier, 2.0 replays twice as fast
+#[derive(Debug, Resource, Clone, Copy)]
+pub(crate) struct PlaybackSpeed(f32);
+
+impl Default for PlaybackSpeed {
+    fn default() -> Self {
+        Self(1.0)
+    }
+}
+
+/// A running playback
+#[derive(Debug, Resource)]
+struct Playback {
+    events: Vec<TimedEvent>,
+    next: usize,
+    elapsed: Duration,
+    chalk: Entity,
+}
+
+/// The chalk drawing the replayed strokes
+#[derive(Component)]
+struct ReplayChalk;
+
+/// Start recording, or stop the running recording
+#[derive(Event)]
+pub(crate) struct ToggleRecordingEvent;
+
+/// Replay the last recording, restarting the running playback if any
+#[derive(Event)]
+pub(crate) struct StartPlaybackEvent;
+
+/// Multiply the playback speed by the given factor
+#[derive(Event)]
+pub(crate) struct ChangePlaybackSpeedEvent(pub f32);
+
+fn handle_toggle_recording_event(
+    mut events: EventReader<ToggleRecordingEvent>,
+    mut state: ResMut<RecordingState>,
+    time: Res<Time>,
+) {
+    for _ in events.read() {
+        if state.recording {
+            state.recording = false;
+            log::info!("recording stopped, {} events", state.events.len());
+            save_recording(&state.events);
+        } else {
+            state.start(time.elapsed());
+            log::info!("recording started");
+        }
+    }
+}
+
+fn handle_change_playback_speed_event(
+    mut events: EventReader<ChangePlaybackSpeedEvent>,
+    mut speed: ResMut<PlaybackSpeed>,
+) {
+    for ChangePlaybackSpeedEvent(factor) in events.read() {
+        speed.0 = (speed.0 * factor).clamp(MIN_PLAYBACK_SPEED, MAX_PLAYBACK_SPEED);
+        log::info!("playback speed {}x", speed.0);
+    }
+}
+
+/// Only local strokes and clears are recorded, not the ones from peers
+fn record(
+    mut state: ResMut<RecordingState>,
+    chalk: Res<LocalChalk>,
+    mut clear_events: EventReader<ClearEvent>,
+    time: Res<Time>,
+) {
+    let cleared: Vec<ClearTarget> = clear_events
+        .read()
+        .filter(|e| e.must_be_forwarded())
+        .map(ClearEvent::target)
+        .collect();
+
+    if !state.recording {
+        return;
+    }
+
+    let now = time.elapsed();
+
+    for target in cleared {
+        state.push(now, DrawCommand::Clear(target));
+    }
+
+    let chalk = *chalk.get();
+
+    if state.last_chalk != Some(chalk) {
+        state.last_chalk = Some(chalk);
+        state.push(now, DrawCommand::Chalk(chalk));
+    }
+}
+
+fn handle_start_playback_event(
+    mut commands: Commands,
+    mut events: EventReader<StartPlaybackEvent>,
+    state: Res<RecordingState>,
+    playback: Option<Res<Playback>>,
+) {
+    if events.read().count() == 0 {
+        return;
+    }
+
+    if state.recording {
+        log::warn!("stop recording before playing it back");
+        return;
+    }
+
+    if let Some(playback) = playback {
+        commands.entity(playback.chalk).despawn();
+    }
+
+    // A recording from a previous session can be replayed too
+    let events = if state.events.is_empty() {
+        load_recording()
+    } else {
+        state.events.clone()
+    };
+
+    if events.is_empty() {
+        log::info!("nothing to play back");
+        commands.remove_resource::<Playback>();
+        return;
+    }
+
+    let chalk = commands
+        .spawn((ReplayChalk, make_chalk(Chalk::default())))
+        .id();
+
+    commands.insert_resource(Playback {
+        events,
+        next: 0,
+        elapsed: Duration::ZERO,
+        chalk,
+    });
+}
+
+#[allow(clippy::cast_possible_truncation)]
+fn play(
+    mut commands: Commands,
+    playback: Option<ResMut<Playback>>,
+    speed: Res<PlaybackSpeed>,
+    time: Res<Time>,
+    mut chalk_q: Query<(&mut Chalk, &Polyline), With<ReplayChalk>>,
+    mut clear_events: EventWriter<ClearEvent>,
+) {
+    let Some(mut playback) = playback else {
+        return;
+    };
+
+    let Ok((mut chalk, pending)) = chalk_q.get_mut(playback.chalk) else {
+        return;
+    };
+
+    // The drawing plugin must see each recorded change only once
+    chalk.updated = false;
+    chalk.just_released = false;
+
+    playback.elapsed += time.delta().mul_f32(speed.0);
+    let elapsed_ms = playback.elapsed.as_millis() as u64;
+
+    // At most one chalk state per frame, otherwise points would be lost.
+    // At high speed the playback may lag behind its clock.
+    let mut applied = false;
+
+    while let Some(&event) = playback.events.get(playback.next) {
+        if event.elapsed_ms > elapsed_ms {
+            break;
+        }
+
+        playback.next += 1;
+
+        match event.command {
+            DrawCommand::Clear(target) => {
+                clear_events.send(ClearEvent::local_only(target));
+            }
+            DrawCommand::Chalk(recorded) => {
+                *chalk = recorded;
+                applied = true;
+                break;
+            }
+        }
+    }
+
+    if applied || playback.next < playback.events.len() {
+        return;
+    }
+
+    // Release the chalk first, so that the last stroke is completed
+    if chalk.pressed {
+        chalk.pressed = false;
+        chalk.just_released = true;
+        return;
+    }
+
+    // The drawing has not completed the last stroke yet, release again
+    if !pending.is_empty() {
+        chalk.just_released = true;
+        return;
+    }
+
+    commands.entity(playback.chalk).despawn();
+    commands.remove_resource::<Playback>();
+}
+
+#[cfg(feature = "lavagna_serde")]
+fn save_recording(events: &[TimedEvent]) {
+    let file = match std::fs::File::create(RECORDING_PATH) {
+        Ok(file) => file,
+        Err(err) => {
+            log::error!("cannot create {RECORDING_PATH}: {err}");
+            return;
+        }
+    };
+
+    match serde_json::to_writer(file, events) {
+        Ok(()) => log::info!("recording saved to {RECORDING_PATH}"),
+        Err(err) => log::error!("cannot write {RECORDING_PATH}: {err}"),
+    }
+}
+
+#[cfg(not(feature = "lavagna_serde"))]
+fn save_recording(_events: &[TimedEvent]) {}
+
+#[cfg(feature = "lavagna_serde")]
+fn load_recording() -> Vec<TimedEvent> {
+    let file = match std::fs::File::open(RECORDING_PATH) {
+        Ok(file) => file,
+        Err(err) => {
+            log::error!("cannot read {RECORDING_PATH}: {err}");
+            return Vec::new();
+        }
+    };
+
+    match serde_json::from_reader(std::io::BufReader::new(file)) {
+        Ok(events) => events,
+        Err(err) => {
+            log::error!("cannot parse {RECORDING_PATH}: {err}");
+            Vec::new()
+        }
+    }
+}
+
+#[cfg(not(feature = "lavagna_serde"))]
+fn load_recording() -> Vec<TimedEvent> {
+    Vec::new()
+}
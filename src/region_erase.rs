@@ -0,0 +1,119 @@
+#![allow(clippy::needless_pass_by_value)]
+
+use bevy::color::palettes::css::GRAY;
+use bevy::prelude::*;
+use bevy::window::{CursorIcon, PrimaryWindow};
+use bevy_prototype_lyon::prelude::*;
+
+use crate::drawing::{dashed_path, ClearEvent, Polyline};
+use crate::local_chalk::LocalChalk;
+
+/// Ctrl + drag selects a rectangle, strokes overlapping it are erased on
+/// release
+pub(crate) struct RegionErasePlugin;
+
+impl Plugin for RegionErasePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RegionSelection>()
+            .add_systems(Startup, setup)
+            .add_systems(Update, update_region_selection)
+            .add_systems(Update, update_erase_cursor);
+    }
+}
+
+/// Dash and gap length of the selection outline, in world units
+const DASH_LENGTH: f32 = 8.0;
+const GAP_LENGTH: f32 = 6.0;
+
+/// Where the drag started, in world coordinates
+#[derive(Debug, Resource, Default)]
+struct RegionSelection {
+    start: Option<Vec2>,
+}
+
+/// The dashed rectangle shown while dragging
+#[derive(Component)]
+struct RegionOutline;
+
+fn setup(mut commands: Commands) {
+    // Above the pending strokes
+    let transform = Transform::from_xyz(0., 0., 999.0);
+
+    commands.spawn((
+        RegionOutline,
+        ShapeBundle {
+            path: PathBuilder::new().build(),
+            spatial: transform.into(),
+            visibility: Visibility::Hidden,
+            ..default()
+        },
+        Stroke::new(GRAY, 1.0),
+    ));
+}
+
+fn update_region_selection(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    chalk: Res<LocalChalk>,
+    mut selection: ResMut<RegionSelection>,
+    mut outline_q: Query<(&mut Path, &mut Visibility), With<RegionOutline>>,
+    mut clear_event: EventWriter<ClearEvent>,
+) {
+    let chalk = chalk.get();
+    let cursor = Vec2::new(chalk.x, chalk.y);
+
+    let ctrl = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+
+    if ctrl && mouse_input.just_pressed(MouseButton::Left) {
+        selection.start = Some(cursor);
+    }
+
+    let Some(start) = selection.start else {
+        return;
+    };
+
+    let rect = Rect::from_corners(start, cursor);
+
+    let Ok((mut path, mut visibility)) = outline_q.get_single_mut() else {
+        return;
+    };
+
+    if mouse_input.pressed(MouseButton::Left) {
+        *path = dashed_path(&Polyline::from(rect), DASH_LENGTH, GAP_LENGTH);
+        *visibility = Visibility::Visible;
+        return;
+    }
+
+    selection.start = None;
+    *visibility = Visibility::Hidden;
+
+    if !rect.is_empty() {
+        clear_event.send(ClearEvent::region(rect));
+    }
+}
+
+/// While Ctrl is held, the cursor shows that dragging erases. Custom cursor
+/// images need a newer bevy, a system icon is used instead. The icon shown
+/// before is kept, and put back when Ctrl is released.
+fn update_erase_cursor(
+    keyboard_input: Res<ButtonInput<KeyCode>>,
+    mut window_q: Query<&mut Window, With<PrimaryWindow>>,
+    mut previous_icon: Local<Option<CursorIcon>>,
+) {
+    let ctrl = keyboard_input.any_pressed([KeyCode::ControlLeft, KeyCode::ControlRight]);
+
+    if ctrl == previous_icon.is_some() {
+        return;
+    }
+
+    let Ok(mut window) = window_q.get_single_mut() else {
+        return;
+    };
+
+    if ctrl {
+        *previous_icon = Some(window.cursor.icon);
+        window.cursor.icon = CursorIcon::Cell;
+    } else if let Some(icon) = previous_icon.take() {
+        window.cursor.icon = icon;
+    }
+}
@@ -0,0 +1,143 @@
+#![allow(clippy::needless_pass_by_value)]
+
+use bevy::color::palettes::css::FUCHSIA;
+use bevy::prelude::*;
+use bevy_prototype_lyon::prelude::*;
+
+use crate::drawing::{Completed, StrokeMeta};
+use crate::Chalk;
+
+/// Select completed strokes from the keyboard, in creation order. Useful
+/// when strokes overlap and clicking is ambiguous.
+pub(crate) struct KeyboardSelectionPlugin;
+
+impl Plugin for KeyboardSelectionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<KeyboardSelectionIndex>()
+            .add_event::<SelectNextStrokeEvent>()
+            .add_event::<SelectPreviousStrokeEvent>()
+            .add_event::<DeselectStrokeEvent>()
+            .add_systems(Update, handle_selection_events)
+            .add_systems(Update, update_selection_outline);
+    }
+}
+
+/// Outline width, added to the stroke width
+const OUTLINE_WIDTH: f32 = 4.0;
+
+/// Index of the selected stroke, among completed strokes sorted by creation
+/// time. `None` when nothing is selected.
+#[derive(Debug, Resource, Default)]
+struct KeyboardSelectionIndex(Option<usize>);
+
+/// Marks the selected stroke
+#[derive(Debug, Component)]
+pub(crate) struct Selected;
+
+/// The highlight drawn behind the selected stroke, a child of it
+#[derive(Debug, Component)]
+struct SelectionOutline;
+
+#[derive(Event)]
+pub(crate) struct SelectNextStrokeEvent;
+
+#[derive(Event)]
+pub(crate) struct SelectPreviousStrokeEvent;
+
+#[derive(Event)]
+pub(crate) struct DeselectStrokeEvent;
+
+#[allow(clippy::cast_possible_wrap, clippy::cast_sign_loss)]
+fn handle_selection_events(
+    mut commands: Commands,
+    mut next_events: EventReader<SelectNextStrokeEvent>,
+    mut previous_events: EventReader<SelectPreviousStrokeEvent>,
+    mut deselect_events: EventReader<DeselectStrokeEvent>,
+    mut index: ResMut<KeyboardSelectionIndex>,
+    strokes: Query<(Entity, &StrokeMeta, &Chalk), With<Completed>>,
+    selected: Query<Entity, With<Selected>>,
+) {
+    // Each event moves the selection by one step
+    let offset = next_events.read().count() as isize - previous_events.read().count() as isize;
+    let deselect = deselect_events.read().count() > 0;
+
+    if offset == 0 && !deselect {
+        return;
+    }
+
+    for entity in &selected {
+        commands.entity(entity).remove::<Selected>();
+    }
+
+    if deselect {
+        index.0 = None;
+        return;
+    }
+
+    let mut sorted: Vec<_> = strokes.iter().collect();
+    sorted.sort_by(|(_, a, _), (_, b, _)| a.created_at().total_cmp(&b.created_at()));
+
+    if sorted.is_empty() {
+        index.0 = None;
+        return;
+    }
+
+    // Selection wraps around. With nothing selected, the first step forward
+    // selects the oldest stroke and the first step backward the newest.
+    let len = sorted.len() as isize;
+    let start = match index.0 {
+        Some(i) => i as isize,
+        None if offset > 0 => -1,
+        None => len,
+    };
+    let i = (start + offset).rem_euclid(len) as usize;
+
+    let (entity, meta, chalk) = sorted[i];
+    commands.entity(entity).insert(Selected);
+    index.0 = Some(i);
+
+    // There is no status bar yet
+    log::info!(
+        "selected stroke {}/{}: created at {:.1}s, color {}, width {}",
+        i + 1,
+        sorted.len(),
+        meta.created_at(),
+        chalk.color.to_hex(),
+        chalk.line_width
+    );
+}
+
+fn update_selection_outline(
+    mut commands: Commands,
+    selected: Query<(Entity, &Path, &Chalk), Added<Selected>>,
+    mut deselected: RemovedComponents<Selected>,
+    outlines: Query<(Entity, &Parent), With<SelectionOutline>>,
+) {
+    for stroke in deselected.read() {
+        for (outline, parent) in &outlines {
+            if parent.get() == stroke {
+                commands.entity(outline).despawn();
+            }
+        }
+    }
+
+    for (stroke, path, chalk) in &selected {
+        // Just behind the stroke
+        let transform = Transform::from_xyz(0., 0., -0.001);
+
+        let outline = commands
+            .spawn((
+                SelectionOutline,
+                ShapeBundle {
+                    path: path.clone(),
+                    spatial: transform.into(),
+                    ..default()
+                },
+                Stroke::new(FUCHSIA, chalk.line_width + OUTLINE_WIDTH),
+                Fill::color(Color::NONE),
+            ))
+            .id();
+
+        commands.entity(stroke).add_child(outline);
+    }
+}
@@ -0,0 +1,14 @@
+//! Colors are serialized as a compact `[red, green, blue, alpha]` array,
+//! instead of the struct representation provided by bevy.
+
+use bevy::color::Srgba;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+pub(crate) fn serialize<S: Serializer>(color: &Srgba, serializer: S) -> Result<S::Ok, S::Error> {
+    [color.red, color.green, color.blue, color.alpha].serialize(serializer)
+}
+
+pub(crate) fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Srgba, D::Error> {
+    let [red, green, blue, alpha] = <[f32; 4]>::deserialize(deserializer)?;
+    Ok(Srgba::new(red, green, blue, alpha))
+}
@@ -0,0 +1,160 @@
+use bevy::math::Vec2;
+
+/// Savitzky-Golay smoothing: each point is replaced by the value at its
+/// position of the polynomial of degree `poly_order` fitted (least squares)
+/// to the `window` points centered on it. Noise is removed while corners are
+/// preserved better than with a moving average.
+///
+/// The point count is preserved. The first and last `window / 2` points,
+/// which have no full window around them, are kept as they are. The points
+/// are returned unchanged if `window` is even, not greater than `poly_order`,
+/// or greater than the number of points.
+pub(crate) fn savitzky_golay(points: &[Vec2], window: usize, poly_order: usize) -> Vec<Vec2> {
+    let mut smoothed = points.to_vec();
+
+    if window % 2 == 0 || window <= poly_order || window > points.len() {
+        return smoothed;
+    }
+
+    let Some(coefficients) = savitzky_golay_coefficients(window, poly_order) else {
+        return smoothed;
+    };
+
+    let half = window / 2;
+
+    for (i, smoothed) in smoothed
+        .iter_mut()
+        .enumerate()
+        .take(points.len() - half)
+        .skip(half)
+    {
+        *smoothed = points[i - half..=i + half]
+            .iter()
+            .zip(&coefficients)
+            .map(|(point, c)| *point * *c)
+            .sum();
+    }
+
+    smoothed
+}
+
+/// Convolution coefficients giving the fitted polynomial at the center of
+/// the window: the first row of `(JᵀJ)⁻¹Jᵀ`, where `J` is the Vandermonde
+/// matrix of the window offsets
+#[allow(
+    clippy::cast_precision_loss,
+    clippy::cast_possible_wrap,
+    clippy::cast_possible_truncation
+)]
+fn savitzky_golay_coefficients(window: usize, poly_order: usize) -> Option<Vec<f32>> {
+    let half = (window / 2) as i64;
+    let size = poly_order + 1;
+
+    let offsets: Vec<f64> = (-half..=half).map(|x| x as f64).collect();
+
+    // JᵀJ, augmented with the identity to invert it
+    let mut matrix = vec![vec![0.0_f64; 2 * size]; size];
+
+    for (row, values) in matrix.iter_mut().enumerate() {
+        for (col, value) in values.iter_mut().take(size).enumerate() {
+            *value = offsets.iter().map(|x| x.powi((row + col) as i32)).sum();
+        }
+        values[size + row] = 1.0;
+    }
+
+    // Gauss-Jordan elimination with partial pivoting
+    for col in 0..size {
+        let pivot =
+            (col..size).max_by(|&a, &b| matrix[a][col].abs().total_cmp(&matrix[b][col].abs()))?;
+
+        if matrix[pivot][col].abs() < f64::EPSILON {
+            return None;
+        }
+
+        matrix.swap(col, pivot);
+
+        let divisor = matrix[col][col];
+        for value in &mut matrix[col] {
+            *value /= divisor;
+        }
+
+        let pivot_row = matrix[col].clone();
+
+        for (row, values) in matrix.iter_mut().enumerate() {
+            if row == col {
+                continue;
+            }
+
+            let factor = values[col];
+            for (value, pivot_value) in values.iter_mut().zip(&pivot_row) {
+                *value -= factor * pivot_value;
+            }
+        }
+    }
+
+    // Only the constant term of the polynomial is needed
+    let first_row = &matrix[0][size..];
+
+    let coefficients = offsets
+        .iter()
+        .map(|x| {
+            let c: f64 = first_row
+                .iter()
+                .enumerate()
+                .map(|(power, inverse)| inverse * x.powi(power as i32))
+                .sum();
+            c as f32
+        })
+        .collect();
+
+    Some(coefficients)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_points(actual: &[Vec2], expected: &[Vec2]) {
+        assert_eq!(actual.len(), expected.len());
+        for (a, e) in actual.iter().zip(expected) {
+            assert!(a.abs_diff_eq(*e, 1e-3), "{a} != {e}");
+        }
+    }
+
+    #[test]
+    #[allow(clippy::cast_precision_loss)]
+    fn quadratic_points_are_kept() {
+        let points: Vec<Vec2> = (0..9)
+            .map(|i| i as f32)
+            .map(|x| Vec2::new(x, x * x / 4.0))
+            .collect();
+
+        assert_points(&savitzky_golay(&points, 5, 2), &points);
+        assert_points(&savitzky_golay(&points, 7, 2), &points);
+    }
+
+    #[test]
+    fn noise_is_reduced() {
+        let points = [
+            Vec2::new(0.0, 0.0),
+            Vec2::new(1.0, 0.0),
+            Vec2::new(2.0, 1.0),
+            Vec2::new(3.0, 0.0),
+            Vec2::new(4.0, 0.0),
+        ];
+
+        let smoothed = savitzky_golay(&points, 5, 2);
+
+        assert!(smoothed[2].y < 1.0);
+        assert_points(&[smoothed[0], smoothed[4]], &[points[0], points[4]]);
+    }
+
+    #[test]
+    fn invalid_windows_keep_the_points() {
+        let points = [Vec2::ZERO, Vec2::X, Vec2::Y, Vec2::ONE, Vec2::NEG_X];
+
+        assert_points(&savitzky_golay(&points, 4, 2), &points);
+        assert_points(&savitzky_golay(&points, 3, 3), &points);
+        assert_points(&savitzky_golay(&points, 7, 2), &points);
+    }
+}
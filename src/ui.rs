@@ -3,7 +3,11 @@
 
 use bevy::prelude::*;
 
-use crate::{drawing::ClearEvent, local_chalk::LocalChalk, Stats};
+use crate::{
+    drawing::ClearEvent,
+    local_chalk::{LocalChalk, SizeStepConfig},
+    Stats,
+};
 
 #[derive(Copy, Clone, Resource)]
 struct UiPluginOpt {
@@ -164,22 +168,24 @@ fn color_btn_system(
 
 fn incr_btn_system(
     mut chalk: ResMut<LocalChalk>,
+    config: Res<SizeStepConfig>,
     mut interaction_query: Query<&Interaction, (Changed<Interaction>, With<IncrementButton>)>,
 ) {
     for interaction in &mut interaction_query {
         if *interaction == Interaction::Pressed {
-            chalk.as_mut().grow();
+            chalk.as_mut().grow(&config);
         }
     }
 }
 
 fn decr_btn_system(
     mut chalk: ResMut<LocalChalk>,
+    config: Res<SizeStepConfig>,
     mut interaction_query: Query<&Interaction, (Changed<Interaction>, With<DecrementButton>)>,
 ) {
     for interaction in &mut interaction_query {
         if *interaction == Interaction::Pressed {
-            chalk.as_mut().shrink();
+            chalk.as_mut().shrink(&config);
         }
     }
 }
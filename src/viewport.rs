@@ -0,0 +1,170 @@
+#![allow(clippy::needless_pass_by_value)]
+
+use bevy::input::mouse::MouseWheel;
+use bevy::prelude::*;
+use bevy::utils::Duration;
+use bevy::window::PrimaryWindow;
+use bevy_pancam::{PanCam, PanCamSystemSet};
+
+use crate::drawing::{Completed, Polyline};
+use crate::local_chalk::mouse_wheel_events;
+use crate::{Chalk, MainCamera};
+
+/// Move the camera smoothly, for example to fit the whole drawing
+pub(crate) struct ViewportPlugin;
+
+impl Plugin for ViewportPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ZoomToFitEvent>()
+            .add_systems(Update, handle_zoom_to_fit_event)
+            .add_systems(
+                Update,
+                cancel_viewport_animation
+                    .after(mouse_wheel_events)
+                    .before(PanCamSystemSet),
+            )
+            .add_systems(Update, animate_viewport.after(PanCamSystemSet));
+    }
+}
+
+const ANIMATION_DURATION: Duration = Duration::from_millis(300);
+
+/// Free space around the drawing when fitting it in the window
+const FIT_MARGIN: f32 = 1.1;
+
+/// A running camera animation. A new one replaces it, rather than being
+/// queued after it.
+#[derive(Resource, Debug)]
+struct ViewportAnimation {
+    start_translation: Vec2,
+    start_scale: f32,
+    target_translation: Vec2,
+    target_scale: f32,
+    timer: Timer,
+}
+
+impl ViewportAnimation {
+    fn new(
+        transform: &Transform,
+        projection: &OrthographicProjection,
+        target: Rect,
+        scale: f32,
+    ) -> Self {
+        Self {
+            start_translation: transform.translation.truncate(),
+            start_scale: projection.scale,
+            target_translation: target.center(),
+            target_scale: scale,
+            timer: Timer::new(ANIMATION_DURATION, TimerMode::Once),
+        }
+    }
+
+    fn apply(&self, transform: &mut Transform, projection: &mut OrthographicProjection) {
+        // Ease out: fast at the beginning, slow when reaching the target
+        let t = 1.0 - (1.0 - self.timer.fraction()).powi(3);
+
+        let translation = self.start_translation.lerp(self.target_translation, t);
+        transform.translation.x = translation.x;
+        transform.translation.y = translation.y;
+
+        projection.scale = self.start_scale + (self.target_scale - self.start_scale) * t;
+    }
+}
+
+/// Show all the completed strokes
+#[derive(Event)]
+pub(crate) struct ZoomToFitEvent;
+
+fn handle_zoom_to_fit_event(
+    mut commands: Commands,
+    mut events: EventReader<ZoomToFitEvent>,
+    strokes: Query<(&Polyline, &Chalk), With<Completed>>,
+    window_q: Query<&Window, With<PrimaryWindow>>,
+    camera_q: Query<(&Transform, &OrthographicProjection, Option<&PanCam>), With<MainCamera>>,
+) {
+    if events.read().count() == 0 {
+        return;
+    }
+
+    let drawing = strokes
+        .iter()
+        .filter_map(|(polyline, chalk)| {
+            let bounds = polyline.bounds()?;
+            Some(bounds.inflate(chalk.line_width / 2.0))
+        })
+        .reduce(|a, b| a.union(b));
+
+    // Nothing to fit
+    let Some(drawing) = drawing else {
+        return;
+    };
+
+    let Ok(window) = window_q.get_single() else {
+        return;
+    };
+
+    let Ok((transform, projection, pan_cam)) = camera_q.get_single() else {
+        return;
+    };
+
+    let size = drawing.size() * FIT_MARGIN;
+    let mut scale = (size.x / window.width()).max(size.y / window.height());
+
+    // Stay within the zoom range allowed to the user
+    if let Some(pan_cam) = pan_cam {
+        scale = scale.max(pan_cam.min_scale);
+        if let Some(max_scale) = pan_cam.max_scale {
+            scale = scale.min(max_scale);
+        }
+    }
+
+    commands.insert_resource(ViewportAnimation::new(
+        transform, projection, drawing, scale,
+    ));
+}
+
+fn animate_viewport(
+    mut commands: Commands,
+    time: Res<Time>,
+    animation: Option<ResMut<ViewportAnimation>>,
+    mut camera_q: Query<(&mut Transform, &mut OrthographicProjection), With<MainCamera>>,
+) {
+    let Some(mut animation) = animation else {
+        return;
+    };
+
+    animation.timer.tick(time.delta());
+
+    if let Ok((mut transform, mut projection)) = camera_q.get_single_mut() {
+        animation.apply(&mut transform, &mut projection);
+    }
+
+    if animation.timer.finished() {
+        commands.remove_resource::<ViewportAnimation>();
+    }
+}
+
+/// Manual pan or zoom wins over the animation. Ctrl+wheel only resizes the
+/// chalk and leaves the camera alone, so it runs after `mouse_wheel_events`,
+/// which consumes those wheel events, and lets the animation go on.
+fn cancel_viewport_animation(
+    mut commands: Commands,
+    mut mouse_wheel_events: EventReader<MouseWheel>,
+    mouse_input: Res<ButtonInput<MouseButton>>,
+    animation: Option<Res<ViewportAnimation>>,
+    pan_cam_q: Query<&PanCam>,
+) {
+    let wheel = mouse_wheel_events.read().count() > 0;
+
+    if animation.is_none() {
+        return;
+    }
+
+    let grab = pan_cam_q
+        .iter()
+        .any(|pan_cam| mouse_input.any_pressed(pan_cam.grab_buttons.iter().copied()));
+
+    if wheel || grab {
+        commands.remove_resource::<ViewportAnimation>();
+    }
+}
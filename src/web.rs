@@ -28,6 +28,7 @@ impl TryFrom<&Request> for CollabOpt {
     fn try_from(request: &Request) -> Result<Self, ()> {
         let mut url: Option<String> = None;
         let mut collab_id: Option<u16> = None;
+        let mut deny_remote_undo = false;
 
         for param in request.0.split('&') {
             let mut param = param.split('=');
@@ -36,6 +37,7 @@ impl TryFrom<&Request> for CollabOpt {
             match key {
                 "collab-url" => url = Some(value.to_owned()),
                 "collab-id" => collab_id = value.parse().ok(),
+                "deny-remote-undo" => deny_remote_undo = value.parse().unwrap_or_default(),
                 _ => (),
             }
         }
@@ -43,7 +45,12 @@ impl TryFrom<&Request> for CollabOpt {
         if let Some(url) = url {
             // If collab-url is set, then collab-id must be set too. Randomize it if not.
             let collab_id = collab_id.unwrap_or_else(|| rand::random());
-            Ok(CollabOpt { url, collab_id })
+            Ok(CollabOpt {
+                url,
+                collab_id,
+                allow_remote_undo: !deny_remote_undo,
+                ice_server: None,
+            })
         } else {
             Err(())
         }
@@ -64,6 +71,7 @@ impl From<&Request> for Opt {
                 "v" | "verbose" => opt.verbose = v.parse().unwrap_or_default(),
                 "dbg" | "show-debug-pane" => opt.show_debug_pane = v.parse().unwrap_or_default(),
                 "ui" => opt.ui = v.parse().unwrap_or_default(),
+                "spectate" => opt.spectate = v.parse().unwrap_or_default(),
                 _ => (),
             }
         }
@@ -0,0 +1,102 @@
+#![allow(clippy::needless_pass_by_value)]
+
+use bevy::prelude::*;
+
+use crate::local_chalk::LocalChalk;
+
+/// The window icon (the favicon on wasm) is a square of the chalk color,
+/// showing the active color in the taskbar
+pub(crate) struct WindowIconPlugin;
+
+impl Plugin for WindowIconPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Update, update_window_icon);
+    }
+}
+
+/// The color of the current icon, to regenerate it only when needed
+#[derive(Default)]
+struct IconColor(Option<Srgba>);
+
+impl IconColor {
+    /// The new color, if it changed
+    fn update(&mut self, chalk: &LocalChalk) -> Option<Srgba> {
+        let color = chalk.color();
+
+        if self.0 == Some(color) {
+            return None;
+        }
+
+        self.0 = Some(color);
+        Some(color)
+    }
+}
+
+#[cfg(not(target_arch = "wasm32"))]
+#[allow(clippy::cast_possible_truncation, clippy::cast_sign_loss)]
+fn update_window_icon(
+    chalk: Res<LocalChalk>,
+    windows: NonSend<bevy::winit::WinitWindows>,
+    mut icon_color: Local<IconColor>,
+) {
+    use winit::window::Icon;
+
+    const ICON_SIZE: u32 = 32;
+
+    if !chalk.is_changed() || windows.windows.is_empty() {
+        return;
+    }
+
+    let Some(color) = icon_color.update(&chalk) else {
+        return;
+    };
+
+    let to_u8 = |c: f32| (c.clamp(0.0, 1.0) * 255.0).round() as u8;
+    let pixel = [
+        to_u8(color.red),
+        to_u8(color.green),
+        to_u8(color.blue),
+        to_u8(color.alpha),
+    ];
+    let rgba = pixel.repeat((ICON_SIZE * ICON_SIZE) as usize);
+
+    let icon = match Icon::from_rgba(rgba, ICON_SIZE, ICON_SIZE) {
+        Ok(icon) => icon,
+        Err(err) => {
+            log::error!("cannot create window icon: {err}");
+            return;
+        }
+    };
+
+    for window in windows.windows.values() {
+        window.set_window_icon(Some(icon.clone()));
+    }
+}
+
+#[cfg(target_arch = "wasm32")]
+fn update_window_icon(chalk: Res<LocalChalk>, mut icon_color: Local<IconColor>) {
+    if !chalk.is_changed() {
+        return;
+    }
+
+    let Some(color) = icon_color.update(&chalk) else {
+        return;
+    };
+
+    // No need to encode a png, a svg data url is enough
+    let href = format!(
+        "data:image/svg+xml,<svg xmlns='http://www.w3.org/2000/svg' viewBox='0 0 1 1'>\
+         <rect width='1' height='1' fill='%23{}'/></svg>",
+        color.to_hex().trim_start_matches('#')
+    );
+
+    let link = web_sys::window()
+        .and_then(|window| window.document())
+        .and_then(|document| document.query_selector("link[rel='icon']").ok().flatten());
+
+    if let Some(link) = link {
+        if let Err(err) = link.set_attribute("href", &href) {
+            log::error!("cannot set favicon: {err:?}");
+        }
+    }
+}